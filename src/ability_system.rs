@@ -0,0 +1,164 @@
+use specs::prelude::*;
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+use super::{roll_damage, WantsToUseAbility, AbilityKind, WantsToAreaAttack, AreaShape, WantsToRaiseDead, CombatStats, SufferDamage,
+    StatusEffects, InBackpack, Equipped, Position, Name, Awareness, AwarenessState, Map, gamelog::GameLog, Unidentified, display_name};
+
+/// How wide a breath attack spreads either side of where it's aimed, in degrees.
+const BREATH_HALF_ANGLE: f32 = 30.0;
+
+/// Carries out the abilities monsters have decided to use. Breath and raising the dead are handed
+/// on to the systems that deal with areas and corpses; everything else happens here.
+pub struct AbilitySystem {}
+
+impl<'a> System<'a> for AbilitySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, GameLog>,
+                        Read<'a, LazyUpdate>,
+                        WriteStorage<'a, WantsToUseAbility>,
+                        WriteStorage<'a, WantsToAreaAttack>,
+                        WriteStorage<'a, WantsToRaiseDead>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, StatusEffects>,
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Unidentified>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, player_entity, map, mut rng, mut log, lazy, mut wants_ability, mut wants_area, mut wants_raise,
+            mut combat_stats, mut inflict_damage, mut statuses, mut backpack, mut awareness, positions, names, equipped, unidentified) = data;
+
+        // Tiles summoned monsters have been promised this turn, since they don't block until spawned
+        let mut taken: Vec<Point> = Vec::new();
+
+        for (entity, ability, pos, name) in (&entities, &wants_ability, &positions, &names).join() {
+            if combat_stats.get(entity).is_none_or(|stats| stats.hp <= 0) { continue; }
+
+            let origin = Point::new(pos.x, pos.y);
+            let seen = map.visible_tiles[map.xy_idx(pos.x, pos.y)];
+            let target_pos = ability.target.and_then(|target| positions.get(target)).map(|at| Point::new(at.x, at.y));
+
+            match ability.kind {
+                AbilityKind::Breath{ radius, n_dice, die_type, kind } => {
+                    let towards = match target_pos {
+                        Some(towards) => towards,
+                        None => continue
+                    };
+                    if seen {
+                        log.entries.push(format!("{} breathes {}!", &name.name, kind.name()));
+                    }
+                    wants_area.insert(entity, WantsToAreaAttack{
+                        origin,
+                        shape: AreaShape::Cone{ towards, radius, half_angle: BREATH_HALF_ANGLE },
+                        n_dice, die_type, bonus: 0, kind, knockback: 0
+                    }).expect("Unable to insert area attack");
+                }
+                AbilityKind::Summon{ count, spawn } => {
+                    let spots = map.free_tiles_around(origin).into_iter()
+                        .filter(|spot| !taken.contains(spot))
+                        .take(count as usize)
+                        .collect::<Vec<_>>();
+                    if spots.is_empty() { continue; }
+                    if seen {
+                        log.entries.push(format!("{} calls for help!", &name.name));
+                    }
+                    for spot in spots {
+                        taken.push(spot);
+                        lazy.exec_mut(move |world| spawn(world, spot.x, spot.y));
+                    }
+                }
+                AbilityKind::HealAllies{ amount, .. } => {
+                    let target = match ability.target {
+                        Some(target) => target,
+                        None => continue
+                    };
+                    if let Some(stats) = combat_stats.get_mut(target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+                    }
+                    if seen {
+                        if target == entity {
+                            log.entries.push(format!("{} heals itself.", &name.name));
+                        } else if let Some(target_name) = names.get(target) {
+                            log.entries.push(format!("{} heals {}.", &name.name, &target_name.name));
+                        }
+                    }
+                }
+                AbilityKind::Spell{ range, n_dice, die_type, kind, inflicts } => {
+                    let towards = match target_pos {
+                        Some(towards) => towards,
+                        None => continue
+                    };
+                    let mut path = map.projectile_path(origin, towards);
+                    path.truncate(range as usize);
+                    let victim = path.iter()
+                        .flat_map(|point| map.entities_at(map.xy_idx(point.x, point.y)).iter())
+                        .find(|other| **other != entity && combat_stats.get(**other).is_some_and(|s| s.hp > 0))
+                        .copied();
+                    let victim = match victim {
+                        Some(victim) => victim,
+                        None => {
+                            if seen {
+                                log.entries.push(format!("{}'s spell fizzles out.", &name.name));
+                            }
+                            continue;
+                        }
+                    };
+
+                    let damage = roll_damage(&mut rng, n_dice, die_type, 0, false);
+                    if seen {
+                        if let Some(victim_name) = names.get(victim) {
+                            log.entries.push(format!("{} blasts {} with a bolt of {}, for {} damage.", &name.name, &victim_name.name, kind.name(), damage));
+                        }
+                    }
+                    SufferDamage::new_damage(&mut inflict_damage, victim, damage, kind);
+                    if let Some((status, turns)) = inflicts {
+                        StatusEffects::add(&mut statuses, victim, status, turns);
+                    }
+                    if let Some(aware) = awareness.get_mut(victim) {
+                        aware.state = AwarenessState::Hunting{ target: entity, last_seen: origin };
+                    }
+                }
+                AbilityKind::Steal => {
+                    let target = match ability.target {
+                        Some(target) => target,
+                        None => continue
+                    };
+                    // Nobody's quick enough to take what you're wearing
+                    let loot = (&entities, &backpack, !&equipped).join()
+                        .filter(|(_item, pack, _worn)| pack.owner == target)
+                        .map(|(item, _pack, _worn)| item)
+                        .collect::<Vec<_>>();
+                    if loot.is_empty() { continue; }
+                    let item = loot[rng.roll_dice(1, loot.len() as i32) as usize - 1];
+                    backpack.insert(item, InBackpack{ owner: entity }).expect("Unable to insert backpack entry");
+
+                    let item_name = display_name(item, &names, &unidentified);
+                    if target == *player_entity {
+                        log.entries.push(format!("{} steals your {}!", &name.name, item_name));
+                    } else if seen {
+                        log.entries.push(format!("{} steals a {}.", &name.name, item_name));
+                    }
+                    if let Some(aware) = awareness.get_mut(target) {
+                        aware.state = AwarenessState::Hunting{ target: entity, last_seen: origin };
+                    }
+                }
+                AbilityKind::RaiseDead{ .. } => {
+                    if let Some(corpse) = ability.target {
+                        wants_raise.insert(entity, WantsToRaiseDead{ corpse }).expect("Unable to insert raise");
+                    }
+                }
+                AbilityKind::Split => {}
+            }
+        }
+
+        wants_ability.clear();
+    }
+}
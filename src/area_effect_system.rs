@@ -0,0 +1,57 @@
+use specs::prelude::*;
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+use super::{roll_damage, WantsToAreaAttack, Knockback, CombatStats, Name, SufferDamage, Awareness, AwarenessState,
+    Map, Position, gamelog::GameLog};
+
+/// Resolves blasts, breaths and other attacks that fill an area. One damage roll is shared by
+/// everything caught in it.
+pub struct AreaAttackSystem {}
+
+impl<'a> System<'a> for AreaAttackSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, WantsToAreaAttack>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, Knockback>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, Position>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut map, mut rng, mut log, mut wants_area, combat_stats, names, mut inflict_damage,
+            mut knockback, mut awareness, positions) = data;
+
+        for (entity, attack) in (&entities, &wants_area).join() {
+            // Where the attack came from, as far as anyone caught in it can tell
+            let attacker_pos = positions.get(entity).map_or(attack.origin, |pos| Point::new(pos.x, pos.y));
+            let damage = roll_damage(&mut rng, attack.n_dice, attack.die_type, attack.bonus, false);
+
+            let victims = map.entities_in_area(attack.origin, attack.shape).into_iter()
+                .filter(|victim| combat_stats.get(*victim).is_some_and(|stats| stats.hp > 0))
+                .collect::<Vec<_>>();
+            for victim in victims {
+                if let Some(name) = names.get(victim) {
+                    log.entries.push(format!("{} is caught in the blast, for {} {} damage.", &name.name, damage, attack.kind.name()));
+                }
+                SufferDamage::new_damage(&mut inflict_damage, victim, damage, attack.kind);
+
+                if attack.knockback > 0 {
+                    knockback.insert(victim, Knockback{ from: attack.origin, distance: attack.knockback })
+                        .expect("Unable to insert knockback");
+                }
+                if victim != entity {
+                    if let Some(aware) = awareness.get_mut(victim) {
+                        aware.state = AwarenessState::Hunting{ target: entity, last_seen: attacker_pos };
+                    }
+                }
+            }
+        }
+
+        wants_area.clear();
+    }
+}
@@ -0,0 +1,360 @@
+//! Offline combat balance simulator. Pits a scripted player against each kind of monster in an
+//! arena, or sends them through a whole generated level, playing out real turns with the game's
+//! own systems: monster AI, abilities, blasts, knockback, noise and all.
+//!
+//! cargo run --release --bin balance -- [--trials N] [--seed N] [--levels] [--csv]
+
+use bracket_lib::prelude::{DijkstraMap, DistanceAlg, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use roguelike::*;
+
+/// Give up on a duel that goes on longer than this; nobody in the game should last that long.
+const MAX_ROUNDS: i32 = 1000;
+/// Give up on a level after this many turns, walking included.
+const MAX_LEVEL_TURNS: i32 = 20_000;
+
+type Spawn = fn(&mut World, i32, i32);
+
+const MATCHUPS: [(&str, Spawn); 12] = [
+    ("Orc", spawner::orc),
+    ("Ogre", spawner::ogre),
+    ("Goblin", spawner::goblin),
+    ("Goblin Archer", spawner::goblin_archer),
+    ("Necromancer", spawner::necromancer),
+    ("Goblin Zombie", |ecs, x, y| spawner::zombie(ecs, x, y, "Goblin")),
+    ("Giant Rat", spawner::giant_rat),
+    ("Goblin Shaman", spawner::goblin_shaman),
+    ("Goblin Cutpurse", spawner::goblin_cutpurse),
+    ("Orc Warchief", spawner::orc_warchief),
+    ("Fire Drake", spawner::fire_drake),
+    ("Ooze", spawner::ooze)
+];
+
+struct Options {
+    trials: i32,
+    seed: u64,
+    levels: bool,
+    csv: bool
+}
+
+fn parse_args() -> Options {
+    let mut options = Options{ trials: 10_000, seed: 1, levels: false, csv: false };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trials" => options.trials = args.next().and_then(|n| n.parse().ok()).expect("--trials needs a number"),
+            "--seed" => options.seed = args.next().and_then(|n| n.parse().ok()).expect("--seed needs a number"),
+            "--levels" => options.levels = true,
+            "--csv" => options.csv = true,
+            _ => {
+                eprintln!("usage: balance [--trials N] [--seed N] [--levels] [--csv]");
+                std::process::exit(2);
+            }
+        }
+    }
+    options
+}
+
+/// A world with everything the game's systems expect, set on `map` with the player at `x`, `y`.
+/// Nothing else is spawned yet.
+fn new_world(seed: u64, map: Map, x: i32, y: i32) -> (State, Entity) {
+    let mut gs = State{ ecs: World::new() };
+    register_components(&mut gs.ecs);
+    let position_reader = gs.ecs.write_storage::<Position>().register_reader();
+    gs.ecs.insert(PositionReader{ reader: position_reader });
+
+    let mut rng = RandomNumberGenerator::seeded(seed);
+    gs.ecs.insert(ItemAppearances::new(&mut rng));
+    gs.ecs.insert(rng);
+    gs.ecs.insert(map);
+    let player = spawner::player(&mut gs.ecs, x, y);
+    gs.ecs.insert(player);
+    gs.ecs.insert(Point::new(x, y));
+    gs.ecs.insert(RunState::PreRun);
+    gs.ecs.insert(GameTurn{ number: 0 });
+    gs.ecs.insert(EntityMemory::default());
+    gs.ecs.insert(NoiseEvents::default());
+    gs.ecs.insert(Reactions::default());
+    gs.ecs.insert(GameLog{ entries: Vec::new() });
+    (gs, player)
+}
+
+/// An open room, big enough for a drake's breath and an archer to back off.
+fn arena() -> Map {
+    let mut map = Map::new(20, 20);
+    for y in 1..19 {
+        for x in 1..19 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+    map.populate_blocked();
+    map
+}
+
+fn hp(ecs: &World, entity: Entity) -> i32 {
+    ecs.read_storage::<CombatStats>().get(entity).map_or(0, |stats| stats.hp)
+}
+
+/// Monsters still standing that the player has to deal with: those hostile to them, and any
+/// that are after them anyway.
+fn foes(ecs: &World) -> Vec<(Entity, Point)> {
+    let player = *ecs.fetch::<Entity>();
+    let reactions = ecs.fetch::<Reactions>();
+    let entities = ecs.entities();
+    let monsters = ecs.read_storage::<Monster>();
+    let stats = ecs.read_storage::<CombatStats>();
+    let positions = ecs.read_storage::<Position>();
+    let factions = ecs.read_storage::<Faction>();
+    let awareness = ecs.read_storage::<Awareness>();
+
+    (&entities, &monsters, &stats, &positions).join()
+        .filter(|(_entity, _monster, stats, _pos)| stats.hp > 0)
+        .filter(|(entity, _monster, _stats, _pos)| {
+            let hostile = factions.get(*entity).is_some_and(|faction| reactions.reaction(Faction::Player, *faction) == Reaction::Hostile);
+            let hunting = awareness.get(*entity).is_some_and(|a| matches!(a.state, AwarenessState::Hunting{ target, .. } if target == player));
+            hostile || hunting
+        })
+        .map(|(entity, _monster, _stats, pos)| (entity, Point::new(pos.x, pos.y)))
+        .collect()
+}
+
+/// The scripted player: hit a foe alongside, otherwise rest up if hurt and nothing's in view,
+/// otherwise walk towards the nearest foe it can get to. Returns false if there's nobody left to
+/// go after.
+fn player_turn(ecs: &mut World, player: Entity) -> bool {
+    let at = *ecs.fetch::<Point>();
+    let foes = foes(ecs);
+
+    if let Some((foe, _foe_at)) = foes.iter().find(|(_foe, foe_at)| DistanceAlg::Pythagoras.distance2d(at, *foe_at) < 1.5) {
+        ecs.write_storage::<WantsToMelee>().insert(player, WantsToMelee{ target: *foe }).expect("Unable to insert attack");
+        return true;
+    }
+
+    let next = {
+        let mut map = ecs.fetch_mut::<Map>();
+        // Walking distance to the nearest foe from every tile; the player's own tile has to be
+        // open for that to reach them
+        let player_idx = map.xy_idx(at.x, at.y);
+        let player_blocked = map.blocked[player_idx];
+        map.blocked[player_idx] = false;
+        let starts = foes.iter().map(|(_foe, foe_at)| map.xy_idx(foe_at.x, foe_at.y)).collect::<Vec<_>>();
+        let distances = DijkstraMap::new(map.width, map.height, &starts, &*map, 1000.0);
+        map.blocked[player_idx] = player_blocked;
+
+        if distances.map[player_idx] == f32::MAX { return false; }
+
+        let hurt = ecs.read_storage::<CombatStats>().get(player).is_some_and(|stats| stats.hp < stats.max_hp);
+        let foe_in_view = foes.iter().any(|(_foe, foe_at)| map.visible_tiles[map.xy_idx(foe_at.x, foe_at.y)]);
+        if hurt && !foe_in_view { return true; }
+
+        match DijkstraMap::find_lowest_exit(&distances, player_idx, &*map) {
+            Some(idx) => Point::new(idx as i32 % map.width, idx as i32 / map.width),
+            // Hemmed in; wait for a gap
+            None => return true
+        }
+    };
+
+    let mut positions = ecs.write_storage::<Position>();
+    let pos = positions.get_mut(player).expect("Player has no position");
+    pos.x = next.x;
+    pos.y = next.y;
+    ecs.write_storage::<Viewshed>().get_mut(player).expect("Player has no viewshed").dirty = true;
+    *ecs.write_resource::<Point>() = next;
+    // Footsteps, as loud as they are in the game
+    ecs.write_resource::<NoiseEvents>().make_noise(next.x, next.y, 2);
+    true
+}
+
+/// Plays out a turn the way `State::tick` does once the player has chosen what to do: the
+/// player's action, then the monsters' go (twice if the player is slowed).
+fn play_turn(gs: &mut State, player: Entity) {
+    *gs.ecs.write_resource::<RunState>() = RunState::PlayerTurn;
+    gs.run_systems();
+    delete_the_dead(&mut gs.ecs);
+
+    loop {
+        *gs.ecs.write_resource::<RunState>() = RunState::MonsterTurn;
+        gs.run_systems();
+        gs.ecs.write_resource::<GameTurn>().number += 1;
+        delete_the_dead(&mut gs.ecs);
+        let slowed = gs.ecs.read_storage::<StatusEffects>().get(player).is_some_and(|s| s.has(StatusKind::Slowed));
+        if !slowed || gs.ecs.fetch::<GameTurn>().number % 2 == 0 { break; }
+    }
+
+    // Nobody's reading
+    gs.ecs.write_resource::<GameLog>().entries.clear();
+}
+
+#[derive(PartialEq)]
+enum Outcome {
+    /// Every foe is down.
+    Won,
+    Died,
+    /// Foes are left standing that the player has no way to reach, say behind a secret door.
+    Stranded,
+    /// Still going when the turn limit came up.
+    TimedOut
+}
+
+struct Duel {
+    outcome: Outcome,
+    turns: i32
+}
+
+/// Plays turns until the player drops or has no foe left to reach, giving up after `max_turns`.
+fn fight(gs: &mut State, player: Entity, max_turns: i32) -> Duel {
+    *gs.ecs.write_resource::<RunState>() = RunState::PreRun;
+    gs.run_systems();
+
+    for turn in 1..=max_turns {
+        if !player_turn(&mut gs.ecs, player) {
+            let outcome = if foes(&gs.ecs).is_empty() { Outcome::Won } else { Outcome::Stranded };
+            return Duel{ outcome, turns: turn - 1 };
+        }
+        play_turn(gs, player);
+        if hp(&gs.ecs, player) < 1 { return Duel{ outcome: Outcome::Died, turns: turn }; }
+    }
+    Duel{ outcome: Outcome::TimedOut, turns: max_turns }
+}
+
+fn foe_hp_left(ecs: &World) -> i32 {
+    foes(ecs).iter().map(|(foe, _at)| hp(ecs, *foe)).sum()
+}
+
+/// Running totals for one row of the report.
+#[derive(Default)]
+struct Tally {
+    trials: i32,
+    wins: i32,
+    /// Stranded or timed out: neither side won.
+    unfinished: i32,
+    turns_to_kill: i32,
+    player_hp_left: i32,
+    turns_to_die: i32,
+    monster_hp_left: i32
+}
+
+impl Tally {
+    fn record(&mut self, duel: &Duel, player_hp: i32, monster_hp: i32) {
+        self.trials += 1;
+        match duel.outcome {
+            Outcome::Won => {
+                self.wins += 1;
+                self.turns_to_kill += duel.turns;
+                self.player_hp_left += player_hp;
+            }
+            Outcome::Died => {
+                self.turns_to_die += duel.turns;
+                self.monster_hp_left += monster_hp;
+            }
+            Outcome::Stranded | Outcome::TimedOut => self.unfinished += 1
+        }
+    }
+
+    /// Win rate and unfinished rate, then the averages for wins and deaths. Averages are `None`
+    /// with nothing to average.
+    #[allow(clippy::type_complexity)]
+    fn summary(&self) -> (f64, f64, Option<f64>, Option<f64>, Option<f64>, Option<f64>) {
+        let losses = self.trials - self.wins - self.unfinished;
+        let average = |total: i32, count: i32| if count > 0 { Some(total as f64 / count as f64) } else { None };
+        (
+            self.wins as f64 / i32::max(1, self.trials) as f64,
+            self.unfinished as f64 / i32::max(1, self.trials) as f64,
+            average(self.turns_to_kill, self.wins),
+            average(self.player_hp_left, self.wins),
+            average(self.turns_to_die, losses),
+            average(self.monster_hp_left, losses)
+        )
+    }
+}
+
+fn run_duels(options: &Options, seeds: &mut RandomNumberGenerator) -> Vec<(String, Tally)> {
+    let mut rows = Vec::new();
+    for (name, spawn) in MATCHUPS.iter() {
+        let mut tally = Tally::default();
+        for _ in 0..options.trials {
+            let (mut gs, player) = new_world(seeds.next_u64(), arena(), 9, 10);
+            spawn(&mut gs.ecs, 10, 10);
+            // Already squared up, so nobody needs to notice anybody
+            {
+                let entities = gs.ecs.entities();
+                let monsters = gs.ecs.read_storage::<Monster>();
+                let mut awareness = gs.ecs.write_storage::<Awareness>();
+                for (monster, _monster) in (&entities, &monsters).join() {
+                    awareness.insert(monster, Awareness{ state: AwarenessState::Hunting{ target: player, last_seen: Point::new(9, 10) } }).expect("Unable to insert awareness");
+                }
+            }
+
+            let result = fight(&mut gs, player, MAX_ROUNDS);
+            tally.record(&result, hp(&gs.ecs, player), foe_hp_left(&gs.ecs));
+        }
+        rows.push((format!("Player vs {}", name), tally));
+    }
+    rows
+}
+
+/// The player starts in the first room of a freshly generated level and hunts down every foe they
+/// can reach, resting whenever they're hurt and nothing's in view. A "win" is clearing the level;
+/// foes left somewhere the player can't get to make it unfinished, not a win.
+fn run_levels(options: &Options, seeds: &mut RandomNumberGenerator) -> Vec<(String, Tally)> {
+    let mut tally = Tally::default();
+    let mut foes_faced = 0;
+    let mut foes_left = 0;
+    let mut foes_out_of_reach = 0;
+
+    for _ in 0..options.trials {
+        let map = Map::new_map_rooms_and_corridors(CorridorSettings::random(&mut RandomNumberGenerator::seeded(seeds.next_u64())));
+        let (x, y) = map.rooms[0].center();
+        let (mut gs, player) = new_world(seeds.next_u64(), map, x, y);
+        let rooms = std::mem::take(&mut gs.ecs.fetch_mut::<Map>().rooms);
+        for room in rooms.iter().skip(1) {
+            spawner::spawn_room(&mut gs.ecs, room);
+        }
+        gs.ecs.fetch_mut::<Map>().rooms = rooms;
+        foes_faced += foes(&gs.ecs).len();
+
+        let result = fight(&mut gs, player, MAX_LEVEL_TURNS);
+        foes_left += foes(&gs.ecs).len();
+        if result.outcome == Outcome::Stranded {
+            foes_out_of_reach += foes(&gs.ecs).len();
+        }
+        tally.record(&result, hp(&gs.ecs, player), foe_hp_left(&gs.ecs));
+    }
+
+    eprintln!("{} foes at the start, {} still standing at the end, {} of them out of reach", foes_faced, foes_left, foes_out_of_reach);
+    vec![("Player vs whole level".to_string(), tally)]
+}
+
+fn print_report(rows: &[(String, Tally)], csv: bool) {
+    let show = |value: Option<f64>, blank: &str| value.map_or(blank.to_string(), |v| format!("{:.1}", v));
+
+    if csv {
+        println!("matchup,trials,player_win_rate,unfinished_rate,avg_turns_to_kill,avg_player_hp_left,avg_turns_to_die,avg_monster_hp_left");
+        for (name, tally) in rows.iter() {
+            let (win_rate, unfinished_rate, kill, player_hp, die, monster_hp) = tally.summary();
+            println!("{},{},{:.4},{:.4},{},{},{},{}", name, tally.trials, win_rate, unfinished_rate,
+                show(kill, ""), show(player_hp, ""), show(die, ""), show(monster_hp, ""));
+        }
+    } else {
+        println!("{:<26} {:>7} {:>7} {:>12} {:>12} {:>12} {:>12} {:>12}", "matchup", "trials", "win %", "unfinished %", "turns/kill", "player hp", "turns/death", "monster hp");
+        for (name, tally) in rows.iter() {
+            let (win_rate, unfinished_rate, kill, player_hp, die, monster_hp) = tally.summary();
+            println!("{:<26} {:>7} {:>7.1} {:>12.1} {:>12} {:>12} {:>12} {:>12}", name, tally.trials, win_rate * 100.0, unfinished_rate * 100.0,
+                show(kill, "-"), show(player_hp, "-"), show(die, "-"), show(monster_hp, "-"));
+        }
+    }
+}
+
+fn main() {
+    let options = parse_args();
+    let mut seeds = RandomNumberGenerator::seeded(options.seed);
+
+    let rows = if options.levels {
+        run_levels(&options, &mut seeds)
+    } else {
+        run_duels(&options, &mut seeds)
+    };
+    print_report(&rows, options.csv);
+}
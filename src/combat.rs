@@ -0,0 +1,54 @@
+use bracket_lib::prelude::RandomNumberGenerator;
+use specs::prelude::*;
+use super::{Equipped, EquipmentSlot, MeleePowerBonus, DefenseBonus, MeleeDamage};
+
+/// Armour class before defense is added: what an attacker with no bonus must roll on a d20.
+const BASE_ARMOUR_CLASS: i32 = 10;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AttackRoll { Fumble, Miss, Hit, Critical }
+
+/// Rolls a d20 + `power` against an armour class of 10 + `defense`. A natural 1 always misses and
+/// a natural 20 always hits, whatever the odds.
+pub fn roll_attack(rng: &mut RandomNumberGenerator, power: i32, defense: i32) -> AttackRoll {
+    let natural = rng.roll_dice(1, 20);
+    if natural == 1 {
+        AttackRoll::Fumble
+    } else if natural == 20 {
+        AttackRoll::Critical
+    } else if natural + power >= BASE_ARMOUR_CLASS + defense {
+        AttackRoll::Hit
+    } else {
+        AttackRoll::Miss
+    }
+}
+
+/// Rolls `n_dice`d`die_type` + `bonus`, doubling the dice on a critical. Anything that hits does
+/// at least 1 damage.
+pub fn roll_damage(rng: &mut RandomNumberGenerator, n_dice: i32, die_type: i32, bonus: i32, critical: bool) -> i32 {
+    let n_dice = if critical { n_dice * 2 } else { n_dice };
+    i32::max(1, rng.roll_dice(n_dice, die_type) + bonus)
+}
+
+/// Everything `owner` has equipped adds up to this much power.
+pub fn power_bonus(owner: Entity, equipped: &ReadStorage<Equipped>, bonuses: &ReadStorage<MeleePowerBonus>) -> i32 {
+    (equipped, bonuses).join()
+        .filter(|(item, _bonus)| item.owner == owner)
+        .map(|(_item, bonus)| bonus.power)
+        .sum()
+}
+
+/// Everything `owner` has equipped adds up to this much defense.
+pub fn defense_bonus(owner: Entity, equipped: &ReadStorage<Equipped>, bonuses: &ReadStorage<DefenseBonus>) -> i32 {
+    (equipped, bonuses).join()
+        .filter(|(item, _bonus)| item.owner == owner)
+        .map(|(_item, bonus)| bonus.defense)
+        .sum()
+}
+
+/// The damage of the weapon `owner` is wielding, if they're wielding one.
+pub fn wielded_damage(owner: Entity, equipped: &ReadStorage<Equipped>, damage: &ReadStorage<MeleeDamage>) -> Option<MeleeDamage> {
+    (equipped, damage).join()
+        .find(|(item, _damage)| item.owner == owner && item.slot == EquipmentSlot::Melee)
+        .map(|(_item, damage)| *damage)
+}
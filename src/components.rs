@@ -1,14 +1,16 @@
 use specs::prelude::*;
 use specs_derive::*;
 use bracket_lib::prelude::{Point, RGB};
+use super::{AreaShape, Faction};
 
 #[derive(Component)]
+#[storage(FlaggedStorage)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Renderable {
     pub glyph: bracket_lib::prelude::FontCharType,
     pub foreground: RGB,
@@ -21,7 +23,7 @@ pub struct Monster {
     // pub name: Name
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone)]
 pub struct Name {
     pub name: String
 }
@@ -39,6 +41,35 @@ pub struct Viewshed {
 #[derive(Component, Debug)]
 pub struct BlocksTile {}
 
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AwarenessState {
+    Asleep,
+    Unaware,
+    /// Heading for something heard or last seen at (x, y).
+    Investigating { x: i32, y: i32 },
+    /// Going after `target`, which was last seen at `last_seen`.
+    Hunting { target: Entity, last_seen: Point }
+}
+
+/// How much a monster knows about what's around it.
+#[derive(Component, Debug)]
+pub struct Awareness {
+    pub state: AwarenessState
+}
+
+/// Added to the difficulty of monsters' rolls to spot you.
+#[derive(Component, Debug)]
+pub struct Stealth {
+    pub value: i32
+}
+
+/// Bonus added to a d20 roll when trying to notice hidden things.
+#[derive(Component, Debug)]
+pub struct Perception {
+    pub value: i32
+}
+
+/// `power` is added to attack rolls and `defense` to armour class.
 #[derive(Component, Debug)]
 pub struct CombatStats {
     pub max_hp: i32,
@@ -47,22 +78,123 @@ pub struct CombatStats {
     pub power: i32
 }
 
+/// Damage dealt by a melee hit: `n_dice`d`die_type` + `bonus`, of the given kind.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct MeleeDamage {
+    pub n_dice: i32,
+    pub die_type: i32,
+    pub bonus: i32,
+    pub kind: DamageType
+}
+
+/// An item that shoots `n_dice`d`die_type` + `bonus` of `kind` damage up to `range` tiles, using
+/// up one of its owner's `Ammunition` per shot.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct RangedWeapon {
+    pub range: i32,
+    pub n_dice: i32,
+    pub die_type: i32,
+    pub bonus: i32,
+    pub kind: DamageType
+}
+
+/// A stack of arrows or bolts; the item is used up when `count` reaches zero.
+#[derive(Component, Debug)]
+pub struct Ammunition {
+    pub count: i32
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct WantsToShoot {
+    pub target: Entity
+}
+
+/// Melee hits from this creature, or blasts from this item, shove the target `distance` tiles
+/// away.
+#[derive(Component, Debug)]
+pub struct KnocksBack {
+    pub distance: i32
+}
+
+/// Being shoved `distance` tiles directly away from `from`. Anything solid in the way stops the
+/// push and hurts.
+#[derive(Component, Debug)]
+pub struct Knockback {
+    pub from: Point,
+    pub distance: i32
+}
+
+/// A blast of `n_dice`d`die_type` + `bonus` damage filling `shape` around `origin`. Everything
+/// caught in it is hit for the same roll, and shoved `knockback` tiles outwards.
+#[derive(Component, Debug)]
+pub struct WantsToAreaAttack {
+    pub origin: Point,
+    pub shape: AreaShape,
+    pub n_dice: i32,
+    pub die_type: i32,
+    pub bonus: i32,
+    pub kind: DamageType,
+    pub knockback: i32
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct WantsToMelee {
     pub target: Entity
 }
 
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum DamageType {
+    Slashing, Piercing, Bludgeoning, Fire, Cold, Poison, Magic
+}
+
+impl DamageType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DamageType::Slashing => "slashing",
+            DamageType::Piercing => "piercing",
+            DamageType::Bludgeoning => "bludgeoning",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Poison => "poison",
+            DamageType::Magic => "magic"
+        }
+    }
+}
+
+/// Passing turns until healed. `hp` is what the player had last turn, so being hurt stops it.
+#[derive(Component, Debug)]
+pub struct Resting {
+    pub hp: i32
+}
+
+/// Resistant creatures take half damage of those types, vulnerable ones double.
+#[derive(Component, Debug, Clone)]
+pub struct Resistances {
+    pub resistant: Vec<DamageType>,
+    pub vulnerable: Vec<DamageType>
+}
+
+impl Resistances {
+    /// What `amount` of `kind` damage actually does to this creature.
+    pub fn apply(&self, amount: i32, kind: DamageType) -> i32 {
+        let mut amount = amount;
+        if self.resistant.contains(&kind) { amount /= 2; }
+        if self.vulnerable.contains(&kind) { amount *= 2; }
+        amount
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct SufferDamage {
-    pub amount: Vec<i32>
+    pub amount: Vec<(i32, DamageType)>
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, kind: DamageType) {
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.amount.push((amount, kind));
         } else {
-            let dmg = SufferDamage { amount: vec![amount]};
+            let dmg = SufferDamage { amount: vec![(amount, kind)]};
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
     }
@@ -71,11 +203,239 @@ impl SufferDamage {
 #[derive(Component, Debug)]
 pub struct Item {}
 
+/// Drunk rather than used.
+#[derive(Component, Debug)]
+pub struct Potion {}
+
+/// Read rather than used.
+#[derive(Component, Debug)]
+pub struct Scroll {}
+
+/// Has to be aimed at a visible tile no further than `range` away.
+#[derive(Component, Debug)]
+pub struct Ranged {
+    pub range: i32
+}
+
+/// Shown as `name` until the player finds out what it really is.
+#[derive(Component, Debug)]
+pub struct Unidentified {
+    pub name: String
+}
+
+/// Reveals what one of its user's unidentified items really is.
+#[derive(Component, Debug)]
+pub struct Identifies {}
+
+/// Used up when used. Items without it can be used again and again.
+#[derive(Component, Debug)]
+pub struct Consumable {}
+
 #[derive(Component, Debug)]
-pub struct Potion {
+pub struct ProvidesHealing {
     pub heal_amount: i32
 }
 
+/// Rolls `n_dice`d`die_type` of `kind` damage against whatever it's used on.
+#[derive(Component, Debug)]
+pub struct InflictsDamage {
+    pub n_dice: i32,
+    pub die_type: i32,
+    pub kind: DamageType
+}
+
+/// Affects everything within `radius` of where it's used, that the centre can see, instead of
+/// just one target.
+#[derive(Component, Debug)]
+pub struct AreaOfEffect {
+    pub radius: i32
+}
+
+/// Sends whatever it's used on to a random spot on the level.
+#[derive(Component, Debug)]
+pub struct Teleports {}
+
+/// Drinking this lets you sense monsters within `range` for `duration` turns.
+#[derive(Component, Debug)]
+pub struct GrantsTelepathy {
+    pub range: i32,
+    pub duration: i32
+}
+
+/// Drinking this lets you sense items within `range` for `duration` turns.
+#[derive(Component, Debug)]
+pub struct GrantsItemDetection {
+    pub range: i32,
+    pub duration: i32
+}
+
+/// Senses monsters within `range`, line of sight or not, until the game turn `expires`.
+#[derive(Component, Debug)]
+pub struct Telepathic {
+    pub range: i32,
+    pub expires: i32
+}
+
+/// Senses items within `range`, line of sight or not, until the game turn `expires`.
+#[derive(Component, Debug)]
+pub struct DetectingItems {
+    pub range: i32,
+    pub expires: i32
+}
+
+/// Drinking this reveals the layout of the whole level.
+#[derive(Component, Debug)]
+pub struct GrantsMagicMapping {}
+
+/// Drinking this makes you forget the parts of the level further than `radius` away.
+#[derive(Component, Debug)]
+pub struct CausesAmnesia {
+    pub radius: i32
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum StatusKind {
+    Poisoned, Confused, Asleep, Hasted, Slowed, Regenerating, Blinded
+}
+
+impl StatusKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusKind::Poisoned => "Poisoned",
+            StatusKind::Confused => "Confused",
+            StatusKind::Asleep => "Asleep",
+            StatusKind::Hasted => "Hasted",
+            StatusKind::Slowed => "Slowed",
+            StatusKind::Regenerating => "Regenerating",
+            StatusKind::Blinded => "Blinded"
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32
+}
+
+/// Temporary conditions and how many game turns each has left to run.
+#[derive(Component, Debug, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>
+}
+
+impl StatusEffects {
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Gives `target` the effect for `turns`, or tops up its duration if it already has it.
+    /// Haste and slow cancel each other out.
+    pub fn add(store: &mut WriteStorage<StatusEffects>, target: Entity, kind: StatusKind, turns: i32) {
+        if store.get(target).is_none() {
+            store.insert(target, StatusEffects::default()).expect("Unable to insert status effects");
+        }
+        let statuses = store.get_mut(target).unwrap();
+
+        let opposite = match kind {
+            StatusKind::Hasted => Some(StatusKind::Slowed),
+            StatusKind::Slowed => Some(StatusKind::Hasted),
+            _ => None
+        };
+        statuses.effects.retain(|effect| Some(effect.kind) != opposite);
+
+        match statuses.effects.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => effect.turns = i32::max(effect.turns, turns),
+            None => statuses.effects.push(StatusEffect{ kind, turns })
+        }
+    }
+}
+
+/// Using this inflicts `kind` on whatever it's used on for `turns` turns.
+#[derive(Component, Debug)]
+pub struct GrantsStatus {
+    pub kind: StatusKind,
+    pub turns: i32
+}
+
+/// The remains of a monster, which rot away at the game turn `rots`.
+#[derive(Component, Debug)]
+pub struct Corpse {
+    pub monster: String,
+    pub rots: i32
+}
+
+/// Undead crumble to dust when destroyed, leaving no corpse behind.
+#[derive(Component, Debug)]
+pub struct Undead {}
+
+/// Something a monster can do besides hitting things.
+#[derive(Copy, Clone, Debug)]
+pub enum AbilityKind {
+    /// A cone of `kind` damage out to `radius` tiles.
+    Breath { radius: i32, n_dice: i32, die_type: i32, kind: DamageType },
+    /// Calls up to `count` monsters built by `spawn` into the free tiles alongside.
+    Summon { count: i32, spawn: fn(&mut World, i32, i32) },
+    /// Heals the most badly hurt ally in view, itself included.
+    HealAllies { range: f32, amount: i32 },
+    /// A bolt that never misses, but hits the first creature in its path. It may also inflict a
+    /// status for some turns.
+    Spell { range: f32, n_dice: i32, die_type: i32, kind: DamageType, inflicts: Option<(StatusKind, i32)> },
+    /// Takes something from the target's backpack.
+    Steal,
+    /// Splits off half its hit points into a copy of itself when hurt. Never chosen; it just
+    /// happens.
+    Split,
+    /// Raises a corpse in view as a zombie.
+    RaiseDead { range: f32 }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ability {
+    pub kind: AbilityKind,
+    /// Game turns to wait between uses.
+    pub cooldown: i32,
+    /// The game turn it can next be used on.
+    pub ready_at: i32
+}
+
+impl Ability {
+    pub fn new(kind: AbilityKind, cooldown: i32) -> Ability {
+        Ability{ kind, cooldown, ready_at: 0 }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Abilities {
+    pub abilities: Vec<Ability>
+}
+
+/// Using an ability, on `target` if it needs one.
+#[derive(Component, Debug, Clone)]
+pub struct WantsToUseAbility {
+    pub kind: AbilityKind,
+    pub target: Option<Entity>
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct WantsToRaiseDead {
+    pub corpse: Entity
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LootItem {
+    HealthPotion, Arrows, Shortbow,
+    /// Whatever `spawner::random_item` comes up with.
+    AnyItem
+}
+
+/// What a monster might leave behind when it dies. Each entry is rolled separately, with its
+/// percent chance of dropping.
+#[derive(Component, Debug)]
+pub struct LootTable {
+    pub drops: Vec<(LootItem, i32)>
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct InBackpack {
     pub owner: Entity
@@ -87,12 +447,133 @@ pub struct WantsToPickupItem {
     pub item: Entity
 }
 
+/// Using `item`, at `target` if it's aimed anywhere. Without a target it's used on yourself.
 #[derive(Component, Debug)]
-pub struct WantsToDrinkPotion {
-    pub potion: Entity
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<Point>
 }
 
 #[derive(Component, Debug, Clone)]
 pub struct WantsToDropItem {
     pub item: Entity
 }
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum EquipmentSlot {
+    Melee, Shield, Head, Body, Feet, Ring
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 6] = [EquipmentSlot::Melee, EquipmentSlot::Shield, EquipmentSlot::Head,
+        EquipmentSlot::Body, EquipmentSlot::Feet, EquipmentSlot::Ring];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Melee => "Weapon",
+            EquipmentSlot::Shield => "Shield",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::Body => "Body",
+            EquipmentSlot::Feet => "Feet",
+            EquipmentSlot::Ring => "Ring"
+        }
+    }
+}
+
+/// Can be worn or wielded in `slot`, one item to a slot.
+#[derive(Component, Debug)]
+pub struct Equippable {
+    pub slot: EquipmentSlot
+}
+
+/// Being worn or wielded by `owner`. Equipped items stay in the owner's backpack.
+#[derive(Component, Debug, Clone)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot
+}
+
+/// Added to the wearer's power while equipped.
+#[derive(Component, Debug)]
+pub struct MeleePowerBonus {
+    pub power: i32
+}
+
+/// Added to the wearer's defense while equipped.
+#[derive(Component, Debug)]
+pub struct DefenseBonus {
+    pub defense: i32
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct WantsToEquip {
+    pub item: Entity
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct WantsToUnequip {
+    pub item: Entity
+}
+
+/// Registers every component with the world. Anything that builds a `World` to run the game's
+/// systems on should call this first.
+pub fn register_components(ecs: &mut World) {
+    ecs.register::<Position>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Monster>();
+    ecs.register::<Name>();
+    ecs.register::<BlocksTile>();
+    ecs.register::<Perception>();
+    ecs.register::<Awareness>();
+    ecs.register::<Stealth>();
+    ecs.register::<Faction>();
+    ecs.register::<CombatStats>();
+    ecs.register::<MeleeDamage>();
+    ecs.register::<WantsToMelee>();
+    ecs.register::<KnocksBack>();
+    ecs.register::<Knockback>();
+    ecs.register::<WantsToAreaAttack>();
+    ecs.register::<RangedWeapon>();
+    ecs.register::<Ammunition>();
+    ecs.register::<WantsToShoot>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<Resistances>();
+    ecs.register::<Item>();
+    ecs.register::<Potion>();
+    ecs.register::<Scroll>();
+    ecs.register::<Unidentified>();
+    ecs.register::<Identifies>();
+    ecs.register::<Consumable>();
+    ecs.register::<Ranged>();
+    ecs.register::<ProvidesHealing>();
+    ecs.register::<InflictsDamage>();
+    ecs.register::<AreaOfEffect>();
+    ecs.register::<Teleports>();
+    ecs.register::<GrantsTelepathy>();
+    ecs.register::<GrantsItemDetection>();
+    ecs.register::<Telepathic>();
+    ecs.register::<DetectingItems>();
+    ecs.register::<GrantsMagicMapping>();
+    ecs.register::<CausesAmnesia>();
+    ecs.register::<StatusEffects>();
+    ecs.register::<Resting>();
+    ecs.register::<GrantsStatus>();
+    ecs.register::<LootTable>();
+    ecs.register::<Corpse>();
+    ecs.register::<Undead>();
+    ecs.register::<Abilities>();
+    ecs.register::<WantsToUseAbility>();
+    ecs.register::<WantsToRaiseDead>();
+    ecs.register::<InBackpack>();
+    ecs.register::<WantsToPickupItem>();
+    ecs.register::<WantsToUseItem>();
+    ecs.register::<WantsToDropItem>();
+    ecs.register::<Equippable>();
+    ecs.register::<Equipped>();
+    ecs.register::<MeleePowerBonus>();
+    ecs.register::<DefenseBonus>();
+    ecs.register::<WantsToEquip>();
+    ecs.register::<WantsToUnequip>();
+}
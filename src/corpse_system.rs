@@ -0,0 +1,75 @@
+use specs::prelude::*;
+use super::{Corpse, WantsToRaiseDead, Position, Name, Map, GameTurn, RunState, gamelog::GameLog, spawner};
+
+/// Removes corpses once they've rotted away.
+pub struct CorpseDecaySystem {}
+
+impl<'a> System<'a> for CorpseDecaySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, GameTurn>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        ReadStorage<'a, Corpse>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (turn, runstate, map, mut log, entities, corpses, positions, names) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        for (entity, corpse, pos) in (&entities, &corpses, &positions).join() {
+            if turn.number < corpse.rots { continue; }
+
+            if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                if let Some(name) = names.get(entity) {
+                    log.entries.push(format!("The {} rots away.", name.name));
+                }
+            }
+            entities.delete(entity).expect("Delete failed");
+        }
+    }
+}
+
+/// Turns corpses that necromancers have picked out into zombies.
+pub struct RaiseDeadSystem {}
+
+impl<'a> System<'a> for RaiseDeadSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Map>,
+                        WriteExpect<'a, GameLog>,
+                        Read<'a, LazyUpdate>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToRaiseDead>,
+                        ReadStorage<'a, Corpse>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, mut log, lazy, entities, mut wants_raise, corpses, positions, names) = data;
+
+        for (entity, raise) in (&entities, &wants_raise).join() {
+            // Two necromancers might have gone for the same body
+            let (corpse, pos) = match (corpses.get(raise.corpse), positions.get(raise.corpse)) {
+                (Some(corpse), Some(pos)) if entities.is_alive(raise.corpse) => (corpse, pos),
+                _ => continue
+            };
+
+            if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                if let (Some(name), Some(corpse_name)) = (names.get(entity), names.get(raise.corpse)) {
+                    log.entries.push(format!("{} raises the {}!", name.name, corpse_name.name));
+                }
+            }
+
+            let (x, y, monster) = (pos.x, pos.y, corpse.monster.clone());
+            lazy.exec_mut(move |world| spawner::zombie(world, x, y, &monster));
+            entities.delete(raise.corpse).expect("Delete failed");
+        }
+
+        wants_raise.clear();
+    }
+}
@@ -0,0 +1,212 @@
+use bracket_lib::prelude::{DistanceAlg, Point, RandomNumberGenerator};
+
+/// Which rooms get joined to which.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum CorridorStrategy {
+    /// Each room joins the one placed before it. This was the only option originally.
+    Sequential,
+    /// Each room joins its closest neighbour, then any separate clusters are bridged.
+    NearestNeighbour,
+    /// The shortest set of corridors that reaches every room, plus a few extra to form loops.
+    SpanningTree { extra_loops: i32 },
+    /// Rooms are split in half along their widest axis, recursively, and each pair of halves
+    /// is joined at its closest rooms.
+    BspSiblings
+}
+
+/// How a single corridor is dug between two points.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum CorridorStyle {
+    /// One horizontal and one vertical run.
+    Dogleg,
+    /// A wandering path that drifts towards its destination.
+    Drunkard
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CorridorSettings {
+    pub strategy: CorridorStrategy,
+    pub style: CorridorStyle,
+    pub width: i32
+}
+
+impl Default for CorridorSettings {
+    fn default() -> CorridorSettings {
+        CorridorSettings{ strategy: CorridorStrategy::Sequential, style: CorridorStyle::Dogleg, width: 1 }
+    }
+}
+
+impl std::str::FromStr for CorridorStrategy {
+    type Err = String;
+
+    /// `sequential`, `nearest`, `mst` (or `mst:N` for N extra loops) or `bsp`.
+    fn from_str(s: &str) -> Result<CorridorStrategy, String> {
+        match s.split_once(':') {
+            Some(("mst", loops)) => loops.parse()
+                .map(|extra_loops| CorridorStrategy::SpanningTree{ extra_loops })
+                .map_err(|_| format!("bad loop count '{}'", loops)),
+            _ => match s {
+                "sequential" => Ok(CorridorStrategy::Sequential),
+                "nearest" => Ok(CorridorStrategy::NearestNeighbour),
+                "mst" => Ok(CorridorStrategy::SpanningTree{ extra_loops: 3 }),
+                "bsp" => Ok(CorridorStrategy::BspSiblings),
+                _ => Err(format!("unknown corridor strategy '{}'", s))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for CorridorStyle {
+    type Err = String;
+
+    /// `dogleg` or `drunkard`.
+    fn from_str(s: &str) -> Result<CorridorStyle, String> {
+        match s {
+            "dogleg" => Ok(CorridorStyle::Dogleg),
+            "drunkard" => Ok(CorridorStyle::Drunkard),
+            _ => Err(format!("unknown corridor style '{}'", s))
+        }
+    }
+}
+
+impl CorridorSettings {
+    pub fn random(rng: &mut RandomNumberGenerator) -> CorridorSettings {
+        let strategy = match rng.roll_dice(1, 4) {
+            1 => CorridorStrategy::Sequential,
+            2 => CorridorStrategy::NearestNeighbour,
+            3 => CorridorStrategy::SpanningTree{ extra_loops: rng.range(2, 6) },
+            _ => CorridorStrategy::BspSiblings
+        };
+        let style = if rng.roll_dice(1, 3) == 1 { CorridorStyle::Drunkard } else { CorridorStyle::Dogleg };
+        let width = if rng.roll_dice(1, 5) == 1 { 2 } else { 1 };
+
+        CorridorSettings{ strategy, style, width }
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    DistanceAlg::Pythagoras.distance2d(a, b)
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    if parent[i] != i {
+        parent[i] = find(parent, parent[i]);
+    }
+    parent[i]
+}
+
+/// Joins the groups containing `a` and `b`. Returns false if they were already joined.
+fn union(parent: &mut [usize], a: usize, b: usize) -> bool {
+    let (root_a, root_b) = (find(parent, a), find(parent, b));
+    if root_a == root_b { return false; }
+    parent[root_b] = root_a;
+    true
+}
+
+/// The closest pair of rooms with one taken from each list.
+fn closest_pair(centers: &[Point], left: &[usize], right: &[usize]) -> (usize, usize) {
+    let mut best = (left[0], right[0]);
+    let mut best_distance = f32::MAX;
+    for &a in left.iter() {
+        for &b in right.iter() {
+            let d = distance(centers[a], centers[b]);
+            if d < best_distance {
+                best_distance = d;
+                best = (a, b);
+            }
+        }
+    }
+    best
+}
+
+/// Decides which pairs of rooms get a corridor, given each room's centre. Every strategy
+/// leaves all rooms reachable from each other.
+pub fn plan_connections(centers: &[Point], strategy: CorridorStrategy, rng: &mut RandomNumberGenerator) -> Vec<(usize, usize)> {
+    let n = centers.len();
+    let mut connections: Vec<(usize, usize)> = Vec::new();
+    if n < 2 { return connections; }
+
+    match strategy {
+        CorridorStrategy::Sequential => {
+            for i in 1..n {
+                connections.push((i - 1, i));
+            }
+        }
+        CorridorStrategy::NearestNeighbour => {
+            let mut parent: Vec<usize> = (0..n).collect();
+            for i in 0..n {
+                let others: Vec<usize> = (0..n).filter(|j| *j != i).collect();
+                let (_, j) = closest_pair(centers, &[i], &others);
+                if union(&mut parent, i, j) {
+                    connections.push((i, j));
+                }
+            }
+
+            // Nearest neighbours can form separate clusters; bridge them to the first room's
+            loop {
+                let root = find(&mut parent, 0);
+                let (joined, apart): (Vec<usize>, Vec<usize>) = (0..n).partition(|i| find(&mut parent, *i) == root);
+                if apart.is_empty() { break; }
+
+                let (a, b) = closest_pair(centers, &joined, &apart);
+                union(&mut parent, a, b);
+                connections.push((a, b));
+            }
+        }
+        CorridorStrategy::SpanningTree{ extra_loops } => {
+            let mut edges: Vec<(usize, usize, f32)> = Vec::new();
+            for a in 0..n {
+                for b in a + 1..n {
+                    edges.push((a, b, distance(centers[a], centers[b])));
+                }
+            }
+            edges.sort_by(|x, y| x.2.total_cmp(&y.2));
+
+            let mut parent: Vec<usize> = (0..n).collect();
+            let mut spare: Vec<(usize, usize)> = Vec::new();
+            for (a, b, _) in edges.iter() {
+                if union(&mut parent, *a, *b) {
+                    connections.push((*a, *b));
+                } else {
+                    spare.push((*a, *b));
+                }
+            }
+
+            // Loops are picked from the shorter spare edges, skipping some so they don't all
+            // bunch up in the densest part of the map
+            let mut added = 0;
+            for edge in spare.iter() {
+                if added >= extra_loops { break; }
+                if rng.range(0, 2) == 0 {
+                    connections.push(*edge);
+                    added += 1;
+                }
+            }
+        }
+        CorridorStrategy::BspSiblings => {
+            let rooms: Vec<usize> = (0..n).collect();
+            bsp_connect(centers, rooms, &mut connections);
+        }
+    }
+
+    connections
+}
+
+fn bsp_connect(centers: &[Point], mut rooms: Vec<usize>, connections: &mut Vec<(usize, usize)>) {
+    if rooms.len() < 2 { return; }
+
+    let min_x = rooms.iter().map(|i| centers[*i].x).min().unwrap();
+    let max_x = rooms.iter().map(|i| centers[*i].x).max().unwrap();
+    let min_y = rooms.iter().map(|i| centers[*i].y).min().unwrap();
+    let max_y = rooms.iter().map(|i| centers[*i].y).max().unwrap();
+    if max_x - min_x >= max_y - min_y {
+        rooms.sort_by_key(|i| centers[*i].x);
+    } else {
+        rooms.sort_by_key(|i| centers[*i].y);
+    }
+
+    let right = rooms.split_off(rooms.len() / 2);
+    connections.push(closest_pair(centers, &rooms, &right));
+    bsp_connect(centers, rooms, connections);
+    bsp_connect(centers, right, connections);
+}
@@ -1,17 +1,65 @@
 use specs::prelude::*;
-use super::{CombatStats, SufferDamage, Player, Name, gamelog::GameLog};
+use super::{CombatStats, SufferDamage, Resistances, StatusEffects, StatusKind, Player, Name, gamelog::GameLog, Position,
+    InBackpack, LootTable, Renderable, Undead, Map, GameTurn, Abilities, AbilityKind, Equipped, spawner};
+
+/// How many turns a corpse lasts before rotting away.
+const CORPSE_DECAY_TURNS: i32 = 150;
 
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
-    type SystemData = ( WriteStorage<'a, CombatStats>,
-                        WriteStorage<'a, SufferDamage>);
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Resistances>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, StatusEffects>,
+                        WriteStorage<'a, Abilities>,
+                        ReadExpect<'a, GameTurn>,
+                        Read<'a, LazyUpdate>);
     
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (entities, mut log, mut stats, mut damage, resistances, names, mut statuses, mut abilities, turn, lazy) = data;
+
+        for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
+            for (amount, kind) in damage.amount.iter() {
+                let taken = match resistances.get(entity) {
+                    Some(resistances) => resistances.apply(*amount, *kind),
+                    None => *amount
+                };
+
+                if taken != *amount {
+                    if let Some(name) = names.get(entity) {
+                        let reaction = if taken < *amount { "resists" } else { "is vulnerable to" };
+                        log.entries.push(format!("{} {} {}, taking {} hp.", &name.name, reaction, kind.name(), taken));
+                    }
+                }
+                stats.hp -= taken;
+            }
+
+            // Getting hurt wakes you up
+            if let Some(status) = statuses.get_mut(entity) {
+                status.effects.retain(|effect| effect.kind != StatusKind::Asleep);
+            }
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+            // Some things come apart in two when hit, if they survive it
+            if stats.hp > 1 {
+                let split = abilities.get_mut(entity).and_then(|abilities| abilities.abilities.iter_mut()
+                    .find(|ability| matches!(ability.kind, AbilityKind::Split) && turn.number >= ability.ready_at));
+                if let Some(split) = split {
+                    split.ready_at = turn.number + split.cooldown;
+                    let name = names.get(entity).map(|name| name.name.clone());
+                    lazy.exec_mut(move |world| {
+                        if spawner::split_off(world, entity) {
+                            if let Some(name) = name {
+                                world.write_resource::<GameLog>().entries.push(format!("{} splits in two!", name));
+                            }
+                        }
+                    });
+                }
+            }
         }
 
         damage.clear();
@@ -25,6 +73,9 @@ pub fn delete_the_dead(ecs: &mut World) {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let names = ecs.read_storage::<Name>();
+        let undead = ecs.read_storage::<Undead>();
+        let positions = ecs.read_storage::<Position>();
+        let map = ecs.fetch::<Map>();
         let entities = ecs.entities();
         let mut log = ecs.write_resource::<GameLog>();
 
@@ -34,8 +85,13 @@ pub fn delete_the_dead(ecs: &mut World) {
                 match player {
                     None => {
                         let victim_name = names.get(entity);
-                        if let Some(victim_name) = victim_name {
-                            log.entries.push(format!("{} is dead.", &victim_name.name));
+                        let seen = positions.get(entity).is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)]);
+                        if let (Some(victim_name), true) = (victim_name, seen) {
+                            if undead.contains(entity) {
+                                log.entries.push(format!("{} crumbles to dust.", &victim_name.name));
+                            } else {
+                                log.entries.push(format!("{} is dead.", &victim_name.name));
+                            }
                         }
                         dead.push(entity)
                     },
@@ -46,6 +102,62 @@ pub fn delete_the_dead(ecs: &mut World) {
     }
     
     for victim in dead {
+        drop_belongings(ecs, victim);
+        leave_remains(ecs, victim);
         ecs.delete_entity(victim).expect("Unable to delete");
     }
 }
+
+/// Leaves a corpse and a bloodstain where a creature died. The undead leave nothing.
+fn leave_remains(ecs: &mut World, victim: Entity) {
+    if ecs.read_storage::<Undead>().contains(victim) { return; }
+
+    let (x, y) = match ecs.read_storage::<Position>().get(victim) {
+        Some(pos) => (pos.x, pos.y),
+        None => return
+    };
+    let name = match ecs.read_storage::<Name>().get(victim) {
+        Some(name) => name.name.clone(),
+        None => return
+    };
+    let foreground = ecs.read_storage::<Renderable>().get(victim).map(|r| r.foreground);
+    let rots = ecs.fetch::<GameTurn>().number + CORPSE_DECAY_TURNS;
+
+    {
+        let mut map = ecs.fetch_mut::<Map>();
+        let idx = map.xy_idx(x, y);
+        map.add_bloodstain(idx);
+    }
+    spawner::corpse(ecs, x, y, &name, foreground, rots);
+}
+
+/// Empties a dead creature's backpack onto the floor where it fell, and adds anything from its
+/// loot table.
+fn drop_belongings(ecs: &mut World, victim: Entity) {
+    let (x, y) = match ecs.read_storage::<Position>().get(victim) {
+        Some(pos) => (pos.x, pos.y),
+        None => return
+    };
+
+    {
+        let entities = ecs.entities();
+        let mut backpack = ecs.write_storage::<InBackpack>();
+        let mut equipped = ecs.write_storage::<Equipped>();
+        let mut positions = ecs.write_storage::<Position>();
+
+        let carried = (&entities, &backpack).join()
+            .filter(|(_item, pack)| pack.owner == victim)
+            .map(|(item, _pack)| item)
+            .collect::<Vec<_>>();
+        for item in carried {
+            backpack.remove(item);
+            equipped.remove(item);
+            positions.insert(item, Position{ x, y }).expect("Unable to insert position");
+        }
+    }
+
+    let table = ecs.write_storage::<LootTable>().remove(victim);
+    if let Some(table) = table {
+        spawner::drop_loot(ecs, &table.drops, x, y);
+    }
+}
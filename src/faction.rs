@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use specs_derive::*;
+
+/// Which side a creature is on.
+#[derive(Component, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Faction {
+    Player, Orcs, Goblins, Undead,
+    /// Hungry things that only care about the player.
+    Beasts,
+    Wildlife
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Reaction {
+    Hostile, Neutral, Friendly
+}
+
+/// How each faction feels about each other faction. Reactions are always mutual, and members of
+/// the same faction are friendly unless told otherwise.
+pub struct Reactions {
+    table: HashMap<(Faction, Faction), Reaction>
+}
+
+impl Reactions {
+    /// Nobody minds anybody else.
+    pub fn empty() -> Reactions {
+        Reactions{ table: HashMap::new() }
+    }
+
+    pub fn set(&mut self, a: Faction, b: Faction, reaction: Reaction) {
+        self.table.insert((a, b), reaction);
+        self.table.insert((b, a), reaction);
+    }
+
+    pub fn reaction(&self, a: Faction, b: Faction) -> Reaction {
+        match self.table.get(&(a, b)) {
+            Some(reaction) => *reaction,
+            None if a == b => Reaction::Friendly,
+            None => Reaction::Neutral
+        }
+    }
+}
+
+impl Default for Reactions {
+    /// Everything hates the player, orcs and goblins hate each other, the undead hate the living,
+    /// and wildlife keeps to itself.
+    fn default() -> Reactions {
+        let mut reactions = Reactions::empty();
+        for monsters in [Faction::Orcs, Faction::Goblins, Faction::Undead, Faction::Beasts] {
+            reactions.set(Faction::Player, monsters, Reaction::Hostile);
+        }
+        reactions.set(Faction::Orcs, Faction::Goblins, Reaction::Hostile);
+        reactions.set(Faction::Undead, Faction::Orcs, Reaction::Hostile);
+        reactions.set(Faction::Undead, Faction::Goblins, Reaction::Hostile);
+        reactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_faction_is_friendly_unless_told_otherwise() {
+        let mut reactions = Reactions::empty();
+        assert_eq!(reactions.reaction(Faction::Orcs, Faction::Orcs), Reaction::Friendly);
+
+        reactions.set(Faction::Orcs, Faction::Orcs, Reaction::Hostile);
+        assert_eq!(reactions.reaction(Faction::Orcs, Faction::Orcs), Reaction::Hostile);
+    }
+
+    #[test]
+    fn strangers_are_neutral() {
+        let reactions = Reactions::empty();
+        assert_eq!(reactions.reaction(Faction::Orcs, Faction::Wildlife), Reaction::Neutral);
+        assert_eq!(reactions.reaction(Faction::Player, Faction::Goblins), Reaction::Neutral);
+    }
+
+    #[test]
+    fn set_is_mutual() {
+        let mut reactions = Reactions::empty();
+        reactions.set(Faction::Beasts, Faction::Wildlife, Reaction::Hostile);
+        assert_eq!(reactions.reaction(Faction::Beasts, Faction::Wildlife), Reaction::Hostile);
+        assert_eq!(reactions.reaction(Faction::Wildlife, Faction::Beasts), Reaction::Hostile);
+
+        reactions.set(Faction::Wildlife, Faction::Beasts, Reaction::Friendly);
+        assert_eq!(reactions.reaction(Faction::Beasts, Faction::Wildlife), Reaction::Friendly);
+    }
+
+    #[test]
+    fn default_table() {
+        let reactions = Reactions::default();
+        for monsters in [Faction::Orcs, Faction::Goblins, Faction::Undead, Faction::Beasts] {
+            assert_eq!(reactions.reaction(Faction::Player, monsters), Reaction::Hostile);
+            assert_eq!(reactions.reaction(monsters, Faction::Player), Reaction::Hostile);
+        }
+        assert_eq!(reactions.reaction(Faction::Orcs, Faction::Goblins), Reaction::Hostile);
+        assert_eq!(reactions.reaction(Faction::Undead, Faction::Orcs), Reaction::Hostile);
+        assert_eq!(reactions.reaction(Faction::Goblins, Faction::Undead), Reaction::Hostile);
+        assert_eq!(reactions.reaction(Faction::Beasts, Faction::Orcs), Reaction::Neutral);
+        assert_eq!(reactions.reaction(Faction::Player, Faction::Wildlife), Reaction::Neutral);
+        assert_eq!(reactions.reaction(Faction::Wildlife, Faction::Undead), Reaction::Neutral);
+        assert_eq!(reactions.reaction(Faction::Undead, Faction::Undead), Reaction::Friendly);
+    }
+}
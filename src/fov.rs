@@ -0,0 +1,139 @@
+use bracket_lib::prelude::Point;
+
+// Symmetric shadowcasting, adapted from https://www.albertford.com/shadowcasting/ (CC0). Unlike
+// bracket-lib's version it reads a plain opacity grid and keeps its slopes as integer fractions,
+// which avoids trait calls and rational arithmetic in the inner loop. Being symmetric means that
+// if A can see B then B can see A, which lets monsters borrow the player's field of view.
+
+/// A slope of `num / den`, with `den` always positive.
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i32,
+    den: i32
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Slope {
+        Slope{ num, den }
+    }
+
+    /// The slope through the left edge of a tile, as used to narrow a scan line.
+    fn of_tile(depth: i32, column: i32) -> Slope {
+        Slope::new(2 * column - 1, 2 * depth)
+    }
+}
+
+struct Scanline {
+    depth: i32,
+    start: Slope,
+    end: Slope
+}
+
+impl Scanline {
+    /// The first and last columns the line covers: depth * start rounded half up, and depth *
+    /// end rounded half down.
+    fn columns(&self) -> (i32, i32) {
+        let first = (2 * self.depth * self.start.num + self.start.den).div_euclid(2 * self.start.den);
+        let last = -((self.end.den - 2 * self.depth * self.end.num).div_euclid(2 * self.end.den));
+        (first, last)
+    }
+
+    fn is_symmetric(&self, column: i32) -> bool {
+        column * self.start.den >= self.depth * self.start.num && column * self.end.den <= self.depth * self.end.num
+    }
+
+    fn next(&self) -> Scanline {
+        Scanline{ depth: self.depth + 1, start: self.start, end: self.end }
+    }
+}
+
+struct Scanner<'a> {
+    origin: Point,
+    quadrant: u8,
+    width: i32,
+    height: i32,
+    opaque: &'a [bool],
+    visible: &'a mut Vec<Point>
+}
+
+impl Scanner<'_> {
+    fn transform(&self, depth: i32, column: i32) -> Point {
+        match self.quadrant {
+            0 => Point::new(self.origin.x + column, self.origin.y - depth),
+            1 => Point::new(self.origin.x + column, self.origin.y + depth),
+            2 => Point::new(self.origin.x + depth, self.origin.y + column),
+            _ => Point::new(self.origin.x - depth, self.origin.y + column)
+        }
+    }
+
+    /// Anything off the edge of the map blocks sight.
+    fn is_opaque(&self, point: Point) -> bool {
+        if point.x < 0 || point.x >= self.width || point.y < 0 || point.y >= self.height { return true; }
+        self.opaque[(point.y * self.width + point.x) as usize]
+    }
+
+    fn reveal(&mut self, point: Point) {
+        if point.x >= 0 && point.x < self.width && point.y >= 0 && point.y < self.height {
+            self.visible.push(point);
+        }
+    }
+
+    fn scan(&mut self, range: i32) {
+        // A tile is in range if its centre is within range + 1/2 of ours
+        let range_limit = (2 * range + 1) * (2 * range + 1);
+        let mut stack = vec![Scanline{ depth: 1, start: Slope::new(-1, 1), end: Slope::new(1, 1) }];
+
+        while let Some(mut line) = stack.pop() {
+            if line.depth > range { continue; }
+
+            let mut prev_opaque: Option<bool> = None;
+            let (first, last) = line.columns();
+            for column in first ..= last {
+                let point = self.transform(line.depth, column);
+                let (dx, dy) = (point.x - self.origin.x, point.y - self.origin.y);
+                if 4 * (dx * dx + dy * dy) > range_limit { continue; }
+
+                let opaque = self.is_opaque(point);
+                if opaque || line.is_symmetric(column) {
+                    self.reveal(point);
+                }
+                if prev_opaque == Some(true) && !opaque {
+                    line.start = Slope::of_tile(line.depth, column);
+                }
+                if prev_opaque == Some(false) && opaque {
+                    let mut next = line.next();
+                    next.end = Slope::of_tile(line.depth, column);
+                    stack.push(next);
+                }
+                prev_opaque = Some(opaque);
+            }
+            if prev_opaque == Some(false) {
+                stack.push(line.next());
+            }
+        }
+    }
+}
+
+/// Every tile visible from `origin` within `range`, on a `width` by `height` grid where
+/// `opaque[idx]` says whether a tile blocks sight. Opaque tiles that are seen (walls) are
+/// included.
+pub fn symmetric_fov(origin: Point, range: i32, width: i32, height: i32, opaque: &[bool]) -> Vec<Point> {
+    let mut visible: Vec<Point> = Vec::with_capacity((4 * range * range) as usize);
+    visible.push(origin);
+
+    for quadrant in 0..4 {
+        let mut scanner = Scanner{ origin, quadrant, width, height, opaque, visible: &mut visible };
+        scanner.scan(range);
+    }
+
+    // Neighbouring quadrants share their diagonal edges
+    visible.sort_unstable_by_key(|p| (p.y, p.x));
+    visible.dedup();
+    visible
+}
+
+/// True if `target` is close enough to `origin` to fall inside a field of view of `range`.
+pub fn in_fov_range(origin: Point, target: Point, range: i32) -> bool {
+    let (dx, dy) = (target.x - origin.x, target.y - origin.y);
+    4 * (dx * dx + dy * dy) <= (2 * range + 1) * (2 * range + 1)
+}
@@ -1,8 +1,11 @@
-use bracket_lib::prelude::{BTerm, Point, VirtualKeyCode, RGB, Rect};
-use bracket_lib::color::{BLACK, GREY, MAGENTA, RED, WHITE, YELLOW};
+use bracket_lib::prelude::{BTerm, DistanceAlg, Point, VirtualKeyCode, RGB};
+use bracket_lib::color::{BLACK, GREEN, GREY, MAGENTA, ORANGE, RED, WHITE, YELLOW};
 use specs::prelude::*;
 
-use super::{CombatStats, Player, GameLog, Map, Name, Position, State, InBackpack};
+use super::{CombatStats, Player, GameLog, Map, Name, Position, State, InBackpack, EntityMemory, GameTurn, Viewshed,
+    RangedWeapon, Ammunition, ranged_weapon_of, StatusEffects, StatusKind, Equipped, EquipmentSlot, MeleeDamage,
+    MeleePowerBonus, DefenseBonus, power_bonus, defense_bonus, wielded_damage, Ranged, AreaOfEffect, AreaShape,
+    Unidentified, display_name};
 
 pub fn draw_ui(ecs: &World, ctx: &mut BTerm) {
     ctx.draw_box(0, 43, 79, 6, RGB::named(WHITE), RGB::named(BLACK));
@@ -15,12 +18,26 @@ pub fn draw_ui(ecs: &World, ctx: &mut BTerm) {
         ctx.print_color(1, 43, RGB::named(RED), RGB::named(BLACK), health);
         ctx.draw_bar_horizontal(10, 43, 30, stats.hp, stats.max_hp, RGB::named(RED), RGB::named(BLACK));
     }
+
+    // Active effects along the top of the box, good ones in green and bad ones in orange
+    let statuses = ecs.read_storage::<StatusEffects>();
+    if let Some(status) = statuses.get(*ecs.fetch::<Entity>()) {
+        let mut x = 42;
+        for effect in status.effects.iter() {
+            let colour = match effect.kind {
+                StatusKind::Hasted | StatusKind::Regenerating => RGB::named(GREEN),
+                _ => RGB::named(ORANGE)
+            };
+            let label = format!("{}({})", effect.kind.name(), effect.turns);
+            if x + label.len() as i32 > 78 { break; }
+            ctx.print_color(x, 43, colour, RGB::named(BLACK), &label);
+            x += label.len() as i32 + 1;
+        }
+    }
     
     let log = ecs.fetch::<GameLog>();
-    let mut y = 44;
-    for s in log.entries.iter().rev() {
-        if y < 49 {ctx.print(2, y, s);}
-        y += 1;
+    for (y, s) in (44..49).zip(log.entries.iter().rev()) {
+        ctx.print(2, y, s);
     }
 
     let mouse_pos = ctx.mouse_pos();
@@ -30,17 +47,48 @@ pub fn draw_ui(ecs: &World, ctx: &mut BTerm) {
 
 fn draw_tooltips(ecs: &World, ctx: &mut BTerm) {
     let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
     let names = ecs.read_storage::<Name>();
+    let unidentified = ecs.read_storage::<Unidentified>();
     let positions = ecs.read_storage::<Position>();
 
     let mouse_pos = ctx.mouse_pos();
     if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height { return; }
     let mut tooltip: Vec<String> = Vec::new();
 
-    for (name, position) in (&names, &positions).join() {
+    for (entity, _name, position) in (&entities, &names, &positions).join() {
         let idx = map.xy_idx(position.x, position.y);
-        if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
-            tooltip.push(name.name.to_string());
+        if position.x == mouse_pos.0 && position.y == mouse_pos.1 {
+            if map.visible_tiles[idx] {
+                tooltip.push(display_name(entity, &names, &unidentified));
+            } else if map.sensed.contains(&entity) {
+                tooltip.push(format!("{} (sensed)", display_name(entity, &names, &unidentified)));
+            }
+        }
+    }
+
+    let mouse_idx = map.xy_idx(mouse_pos.0, mouse_pos.1);
+    if !map.visible_tiles[mouse_idx] {
+        let memory = ecs.fetch::<EntityMemory>();
+        let turn = ecs.fetch::<GameTurn>();
+        // Anything sensed has already been listed where it really is
+        let remembered = memory.entities.iter()
+            .filter(|(entity, m)| m.x == mouse_pos.0 && m.y == mouse_pos.1 && !map.sensed.contains(entity))
+            .map(|(_entity, m)| m);
+        for remembered in remembered {
+            if let Some(name) = &remembered.name {
+                let ago = turn.number - remembered.last_seen;
+                match ago {
+                    0 => tooltip.push(format!("{} (just seen)", name)),
+                    1 => tooltip.push(format!("{} (last seen 1 turn ago)", name)),
+                    _ => tooltip.push(format!("{} (last seen {} turns ago)", name, ago))
+                }
+            }
+        }
+    }
+    if map.revealed_tiles[mouse_idx] {
+        if let Some(label) = map.room_at(mouse_pos.0, mouse_pos.1).and_then(|room| room.theme.label()) {
+            tooltip.push(label.to_string());
         }
     }
 
@@ -55,32 +103,28 @@ fn draw_tooltips(ecs: &World, ctx: &mut BTerm) {
         if mouse_pos.0 > 40 {
             let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
             let left_x = mouse_pos.0 - width;
-            let mut y = mouse_pos.1;
 
-            for ttip in tooltip.iter() {
+            for (y, ttip) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(left_x, y, WHITE, GREY, ttip);
                 let padding = (width - ttip.len() as i32) - 1;
                 for i in 0..padding {
-                    ctx.print_color(arrow_pos.x - i, y, WHITE, GREY, &" ".to_string());
+                    ctx.print_color(arrow_pos.x - i, y, WHITE, GREY, " ");
                 }
-                y += 1;
             }
 
-            ctx.print_color(arrow_pos.x, arrow_pos.y, WHITE, GREY, &"->".to_string());
+            ctx.print_color(arrow_pos.x, arrow_pos.y, WHITE, GREY, "->");
         } else {
             let arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
             let left_x = mouse_pos.0 + 3;
-            let mut y = mouse_pos.1;
 
-            for ttip in tooltip.iter() {
+            for (y, ttip) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(left_x + 1, y, WHITE, GREY, ttip);
                 let padding = (width - ttip.len() as i32) - 1;
                 for i in 0..padding {
-                    ctx.print_color(arrow_pos.x + 1 + i, y, WHITE, GREY, &" ".to_string());
+                    ctx.print_color(arrow_pos.x + 1 + i, y, WHITE, GREY, " ");
                 }
-                y += 1;
             }
-            ctx.print_color(arrow_pos.x, arrow_pos.y, WHITE, GREY, &"<-".to_string());
+            ctx.print_color(arrow_pos.x, arrow_pos.y, WHITE, GREY, "<-");
         }
     }
 }
@@ -88,31 +132,95 @@ fn draw_tooltips(ecs: &World, ctx: &mut BTerm) {
 #[derive(PartialEq, Copy, Clone)]
 pub enum ItemMenuResult { Cancel, NoResponse, Selected }
 
+/// How an item is listed in the inventory: with how many are left in a stack, and whether it's
+/// being worn.
+fn item_label(item: Entity, name: &str, ammunition: &ReadStorage<Ammunition>, equipped: &ReadStorage<Equipped>) -> String {
+    let mut label = match ammunition.get(item) {
+        Some(ammo) => format!("{} ({})", name, ammo.count),
+        None => name.to_string()
+    };
+    if equipped.contains(item) {
+        label.push_str(" (equipped)");
+    }
+    label
+}
+
+fn describe_damage(damage: &MeleeDamage) -> String {
+    match damage.bonus {
+        0 => format!("{}d{} {}", damage.n_dice, damage.die_type, damage.kind.name()),
+        bonus => format!("{}d{}{:+} {}", damage.n_dice, damage.die_type, bonus, damage.kind.name())
+    }
+}
+
+/// The player's stats with their equipment counted in, and what they're wearing in each slot.
+pub fn show_character(gs: &mut State, ctx: &mut BTerm) -> ItemMenuResult {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let entities = gs.ecs.entities();
+    let names = gs.ecs.read_storage::<Name>();
+    let combat_stats = gs.ecs.read_storage::<CombatStats>();
+    let melee_damage = gs.ecs.read_storage::<MeleeDamage>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let power_bonuses = gs.ecs.read_storage::<MeleePowerBonus>();
+    let defense_bonuses = gs.ecs.read_storage::<DefenseBonus>();
+
+    let (x, y) = (20, 14);
+    ctx.draw_box(x, y - 2, 39, 16, RGB::named(WHITE), RGB::named(BLACK));
+    ctx.print_color(x + 3, y - 2, RGB::named(YELLOW), RGB::named(BLACK), "Character");
+    ctx.print_color(x + 3, y + 14, RGB::named(YELLOW), RGB::named(BLACK), "ESCAPE to close");
+
+    if let Some(stats) = combat_stats.get(*player_entity) {
+        let power = power_bonus(*player_entity, &equipped, &power_bonuses);
+        let defense = defense_bonus(*player_entity, &equipped, &defense_bonuses);
+        let damage = wielded_damage(*player_entity, &equipped, &melee_damage).or_else(|| melee_damage.get(*player_entity).copied());
+
+        ctx.print(x + 2, y, format!("HP: {}/{}", stats.hp, stats.max_hp));
+        ctx.print(x + 2, y + 1, format!("Power: {} ({}{:+})", stats.power + power, stats.power, power));
+        ctx.print(x + 2, y + 2, format!("Defense: {} ({}{:+})", stats.defense + defense, stats.defense, defense));
+        if let Some(damage) = damage {
+            ctx.print(x + 2, y + 3, format!("Damage: {}", describe_damage(&damage)));
+        }
+    }
+
+    ctx.print_color(x + 2, y + 5, RGB::named(YELLOW), RGB::named(BLACK), "Equipment");
+    for (row, slot) in EquipmentSlot::ALL.iter().enumerate() {
+        let worn = (&entities, &equipped, &names).join()
+            .find(|(_item, worn, _name)| worn.owner == *player_entity && worn.slot == *slot)
+            .map_or("-".to_string(), |(_item, _worn, name)| name.name.clone());
+        ctx.print(x + 2, y + 6 + row as i32, format!("{:<8} {}", format!("{}:", slot.name()), worn));
+    }
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::C) => ItemMenuResult::Cancel,
+        _ => ItemMenuResult::NoResponse
+    }
+}
+
 pub fn show_inventory(gs: &mut State, ctx: &mut BTerm) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
+    let unidentified = gs.ecs.read_storage::<Unidentified>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
+    let ammunition = gs.ecs.read_storage::<Ammunition>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
     let entities = gs.ecs.entities();
 
     let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity);
     let count = inventory.count();
 
-    let mut y = (25 - (count / 2)) as i32;
+    let y = (25 - (count / 2)) as i32;
     ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(WHITE), RGB::named(BLACK));
     ctx.print_color(18, y-2, RGB::named(YELLOW), RGB::named(BLACK), "Inventory");
     ctx.print_color(18, y+count as i32+1, RGB::named(YELLOW), RGB::named(BLACK), "ESCAPE to cancel");
 
     let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity) {
+    for (j, (entity, _pack, _name)) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity).enumerate() {
+        let y = y + j as i32;
         ctx.set(17, y, RGB::named(WHITE), RGB::named(BLACK), bracket_lib::prelude::to_cp437('('));
         ctx.set(18, y, RGB::named(YELLOW), RGB::named(BLACK), 97+j as bracket_lib::prelude::FontCharType);
         ctx.set(19, y, RGB::named(WHITE), RGB::named(BLACK), bracket_lib::prelude::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(entity, &display_name(entity, &names, &unidentified), &ammunition, &equipped));
         equippable.push(entity);
-        y+=1;
-        j+=1;
     }
 
     match ctx.key {
@@ -135,28 +243,29 @@ pub fn show_inventory(gs: &mut State, ctx: &mut BTerm) -> (ItemMenuResult, Optio
 pub fn drop_item_menu(gs: &mut State, ctx: &mut BTerm) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
+    let unidentified = gs.ecs.read_storage::<Unidentified>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
+    let ammunition = gs.ecs.read_storage::<Ammunition>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
     let entities = gs.ecs.entities();
 
     let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity);
     let count = inventory.count();
 
-    let mut y = (25 - (count/2)) as i32;
+    let y = (25 - (count/2)) as i32;
     ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(WHITE), RGB::named(BLACK));
     ctx.print_color(18, y-2, RGB::named(YELLOW), RGB::named(BLACK), "Drop which item?");
     ctx.print_color(18, y+count as i32+1, RGB::named(YELLOW), RGB::named(BLACK), "ESCAPE to cancel");
 
     let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity) {
+    for (j, (entity, _pack, _name)) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity).enumerate() {
+        let y = y + j as i32;
         ctx.set(17, y, RGB::named(WHITE), RGB::named(BLACK), bracket_lib::prelude::to_cp437('('));
         ctx.set(18, y, RGB::named(YELLOW), RGB::named(BLACK), 97+j as bracket_lib::prelude::FontCharType);
         ctx.set(19, y, RGB::named(WHITE), RGB::named(BLACK), bracket_lib::prelude::to_cp437('('));
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(entity, &display_name(entity, &names, &unidentified), &ammunition, &equipped));
         equippable.push(entity);
-        y += 1;
-        j += 1;
     }
 
     match ctx.key {
@@ -175,3 +284,206 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut BTerm) -> (ItemMenuResult, Optio
         }
     }
 }
+
+/// Everything the player could shoot at right now, nearest first: visible creatures in range of
+/// their ranged weapon with a clear line of fire.
+pub fn ranged_targets(ecs: &World) -> Vec<Entity> {
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let weapons = ecs.read_storage::<RangedWeapon>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let positions = ecs.read_storage::<Position>();
+
+    let range = match ranged_weapon_of(*player_entity, &entities, &backpack, &weapons) {
+        Some(weapon) => weapon.range as f32,
+        None => return Vec::new()
+    };
+
+    let mut targets = (&entities, &combat_stats, &positions).join()
+        .filter(|(entity, _stats, pos)| {
+            let target = Point::new(pos.x, pos.y);
+            *entity != *player_entity
+                && map.visible_tiles[map.xy_idx(pos.x, pos.y)]
+                && DistanceAlg::Pythagoras.distance2d(*player_pos, target) <= range
+                && map.has_line_of_fire(*player_pos, target)
+        })
+        .map(|(entity, _stats, pos)| (entity, DistanceAlg::Pythagoras.distance2d(*player_pos, Point::new(pos.x, pos.y))))
+        .collect::<Vec<_>>();
+    targets.sort_by(|a, b| a.1.total_cmp(&b.1));
+    targets.into_iter().map(|(entity, _distance)| entity).collect()
+}
+
+/// Lets the player pick something to shoot, starting from `current`. Tab cycles through targets
+/// and the mouse picks one directly. Returns `NoResponse` with the target to show next frame
+/// until the player fires or gives up.
+pub fn ranged_target(gs: &mut State, ctx: &mut BTerm, current: Option<Entity>) -> (ItemMenuResult, Option<Entity>) {
+    let targets = ranged_targets(&gs.ecs);
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let player_pos = gs.ecs.fetch::<Point>();
+    let map = gs.ecs.fetch::<Map>();
+    let viewsheds = gs.ecs.read_storage::<Viewshed>();
+    let positions = gs.ecs.read_storage::<Position>();
+    let entities = gs.ecs.entities();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let weapons = gs.ecs.read_storage::<RangedWeapon>();
+
+    ctx.print_color(5, 0, RGB::named(YELLOW), RGB::named(BLACK), "Fire at what? TAB cycles, ENTER or click fires, ESCAPE cancels");
+
+    // Shade every visible tile within range
+    if let (Some(viewshed), Some(weapon)) = (viewsheds.get(*player_entity), ranged_weapon_of(*player_entity, &entities, &backpack, &weapons)) {
+        for tile in viewshed.visible_tiles.iter() {
+            if DistanceAlg::Pythagoras.distance2d(*player_pos, *tile) <= weapon.range as f32 {
+                ctx.set_bg(tile.x, tile.y, RGB::from_f32(0.0, 0.0, 0.4));
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        ctx.print_color(5, 1, RGB::named(RED), RGB::named(BLACK), "Nothing in range.");
+    }
+
+    let mut current = current.filter(|target| targets.contains(target)).or(targets.first().copied());
+
+    // The mouse takes over whenever it's on a target
+    let mouse_pos = ctx.mouse_pos();
+    let under_mouse = targets.iter()
+        .find(|target| positions.get(**target).is_some_and(|pos| pos.x == mouse_pos.0 && pos.y == mouse_pos.1))
+        .copied();
+    if under_mouse.is_some() {
+        current = under_mouse;
+    }
+
+    if let Some(target_pos) = current.and_then(|target| positions.get(target)) {
+        let target_pos = Point::new(target_pos.x, target_pos.y);
+        for point in map.projectile_path(*player_pos, target_pos) {
+            ctx.set_bg(point.x, point.y, RGB::from_f32(0.5, 0.5, 0.0));
+        }
+        ctx.set_bg(target_pos.x, target_pos.y, RGB::named(RED));
+    }
+
+    if ctx.left_click && under_mouse.is_some() {
+        return (ItemMenuResult::Selected, under_mouse);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, current),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+                VirtualKeyCode::Tab => {
+                    let next = current
+                        .and_then(|target| targets.iter().position(|t| *t == target))
+                        .map(|i| targets[(i + 1) % targets.len()]);
+                    (ItemMenuResult::NoResponse, next)
+                }
+                VirtualKeyCode::Return | VirtualKeyCode::F if current.is_some() => (ItemMenuResult::Selected, current),
+                _ => (ItemMenuResult::NoResponse, current)
+            }
+        }
+    }
+}
+
+/// Where the player could aim `item`: visible tiles within its range that nothing solid hides.
+fn item_target_tiles(ecs: &World, item: Entity) -> Vec<Point> {
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let range = ecs.read_storage::<Ranged>().get(item).map_or(0, |ranged| ranged.range);
+
+    match viewsheds.get(*player_entity) {
+        None => Vec::new(),
+        Some(viewshed) => viewshed.visible_tiles.iter()
+            .filter(|tile| DistanceAlg::Pythagoras.distance2d(*player_pos, **tile) <= range as f32
+                && map.has_line_of_fire(*player_pos, **tile))
+            .copied()
+            .collect()
+    }
+}
+
+/// Creatures the player could aim `item` at, nearest first.
+pub fn item_targets(ecs: &World, item: Entity) -> Vec<Point> {
+    let tiles = item_target_tiles(ecs, item);
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let positions = ecs.read_storage::<Position>();
+
+    let mut targets = (&entities, &combat_stats, &positions).join()
+        .filter(|(entity, _stats, _pos)| *entity != *player_entity)
+        .map(|(_entity, _stats, pos)| Point::new(pos.x, pos.y))
+        .filter(|target| tiles.contains(target))
+        .collect::<Vec<_>>();
+    targets.sort_by(|a, b| DistanceAlg::Pythagoras.distance2d(*player_pos, *a)
+        .total_cmp(&DistanceAlg::Pythagoras.distance2d(*player_pos, *b)));
+    targets
+}
+
+/// Lets the player pick a tile to aim `item` at, starting from `current`. Tab cycles through
+/// creatures in range and the mouse can pick any valid tile. Anything the item would catch in its
+/// area is shown around the chosen tile.
+pub fn item_target(gs: &mut State, ctx: &mut BTerm, item: Entity, current: Option<Point>) -> (ItemMenuResult, Option<Point>) {
+    let tiles = item_target_tiles(&gs.ecs, item);
+    let targets = item_targets(&gs.ecs, item);
+    let player_pos = *gs.ecs.fetch::<Point>();
+    let mut map = gs.ecs.fetch_mut::<Map>();
+    let names = gs.ecs.read_storage::<Name>();
+    let unidentified = gs.ecs.read_storage::<Unidentified>();
+    let area_of_effect = gs.ecs.read_storage::<AreaOfEffect>();
+    let range = gs.ecs.read_storage::<Ranged>().get(item).map_or(0, |ranged| ranged.range);
+    let item_name = display_name(item, &names, &unidentified);
+
+    ctx.print_color(5, 0, RGB::named(YELLOW), RGB::named(BLACK),
+        format!("Aim the {} (range {}). TAB cycles, ENTER or click uses, ESCAPE cancels", item_name, range));
+
+    for tile in tiles.iter() {
+        ctx.set_bg(tile.x, tile.y, RGB::from_f32(0.0, 0.0, 0.4));
+    }
+
+    let mut current = current.filter(|target| tiles.contains(target)).or(targets.first().copied());
+
+    // The mouse takes over whenever it's somewhere the item can reach
+    let mouse_pos = Point::new(ctx.mouse_pos().0, ctx.mouse_pos().1);
+    let under_mouse = Some(mouse_pos).filter(|tile| tiles.contains(tile));
+    if under_mouse.is_some() {
+        current = under_mouse;
+    }
+
+    if let Some(target) = current {
+        for point in map.projectile_path(player_pos, target) {
+            ctx.set_bg(point.x, point.y, RGB::from_f32(0.5, 0.5, 0.0));
+        }
+        // Showing the blast would give away what an unidentified item is; all it gets is its range
+        if let (Some(area), false) = (area_of_effect.get(item), unidentified.contains(item)) {
+            for tile in map.area_tiles(target, AreaShape::Radius{ radius: area.radius }) {
+                ctx.set_bg(tile.x, tile.y, RGB::named(ORANGE));
+            }
+        }
+        ctx.set_bg(target.x, target.y, RGB::named(RED));
+    }
+
+    if ctx.left_click && under_mouse.is_some() {
+        return (ItemMenuResult::Selected, under_mouse);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, current),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+                VirtualKeyCode::Tab if !targets.is_empty() => {
+                    let next = current
+                        .and_then(|target| targets.iter().position(|t| *t == target))
+                        .map_or(targets[0], |i| targets[(i + 1) % targets.len()]);
+                    (ItemMenuResult::NoResponse, Some(next))
+                }
+                VirtualKeyCode::Return if current.is_some() => (ItemMenuResult::Selected, current),
+                _ => (ItemMenuResult::NoResponse, current)
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::{HashMap, HashSet};
+use bracket_lib::prelude::RandomNumberGenerator;
+use bracket_lib::color::{BLUE, BROWN1, CYAN, GOLD, GREEN, ORANGE, PINK, RED, SILVER, VIOLET};
+use specs::prelude::*;
+use specs::storage::GenericReadStorage;
+use super::{Name, Unidentified, EntityMemory};
+
+const POTION_LOOKS: [&str; 8] = ["bubbling", "murky", "fizzy", "cloudy", "glowing", "oily", "smoky", "swirling"];
+
+const POTION_COLOURS: [(&str, (u8, u8, u8)); 10] = [
+    ("blue", BLUE), ("green", GREEN), ("red", RED), ("violet", VIOLET), ("pink", PINK),
+    ("orange", ORANGE), ("cyan", CYAN), ("golden", GOLD), ("rust", BROWN1), ("silver", SILVER)
+];
+
+const SCROLL_SYLLABLES: [&str; 16] = ["ab", "ra", "ka", "dab", "xyz", "zy", "foo", "ex", "nil", "por", "tem", "ul", "vo", "qua", "zim", "rek"];
+
+/// How many scroll labels to make up at the start; far more than there are kinds of scroll.
+const SCROLL_LABELS: usize = 40;
+
+/// What an unidentified potion looks like.
+#[derive(Clone)]
+pub struct PotionLook {
+    pub name: String,
+    pub colour: (u8, u8, u8)
+}
+
+/// What potions and scrolls look like to someone who doesn't know what they are. Every item with
+/// the same real name shares one appearance, handed out the first time one is spawned from pools
+/// shuffled when the game starts.
+pub struct ItemAppearances {
+    potions: Vec<PotionLook>,
+    scrolls: Vec<String>,
+    assigned_potions: HashMap<String, PotionLook>,
+    assigned_scrolls: HashMap<String, String>,
+    identified: HashSet<String>
+}
+
+fn shuffle<T>(rng: &mut RandomNumberGenerator, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        let j = rng.range(0, i as i32 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+impl ItemAppearances {
+    pub fn new(rng: &mut RandomNumberGenerator) -> ItemAppearances {
+        let mut potions = POTION_LOOKS.iter()
+            .flat_map(|look| POTION_COLOURS.iter().map(move |(colour_name, colour)| PotionLook{
+                name: format!("{} {} potion", look, colour_name),
+                colour: *colour
+            }))
+            .collect::<Vec<_>>();
+        shuffle(rng, &mut potions);
+
+        let mut scrolls: Vec<String> = Vec::new();
+        while scrolls.len() < SCROLL_LABELS {
+            let syllables = rng.range(2, 4);
+            let label = (0..syllables)
+                .map(|_| SCROLL_SYLLABLES[rng.range(0, SCROLL_SYLLABLES.len() as i32) as usize])
+                .collect::<String>()
+                .to_uppercase();
+            let label = format!("scroll labelled {}", label);
+            if !scrolls.contains(&label) {
+                scrolls.push(label);
+            }
+        }
+
+        ItemAppearances{
+            potions, scrolls,
+            assigned_potions: HashMap::new(),
+            assigned_scrolls: HashMap::new(),
+            identified: HashSet::new()
+        }
+    }
+
+    /// How every potion called `name` looks, whether or not the player knows what it is.
+    pub fn potion(&mut self, name: &str) -> PotionLook {
+        if !self.assigned_potions.contains_key(name) {
+            let look = self.potions.pop().expect("Ran out of potion appearances");
+            self.assigned_potions.insert(name.to_string(), look);
+        }
+        self.assigned_potions[name].clone()
+    }
+
+    /// What every scroll called `name` is labelled.
+    pub fn scroll(&mut self, name: &str) -> String {
+        if !self.assigned_scrolls.contains_key(name) {
+            let label = self.scrolls.pop().expect("Ran out of scroll labels");
+            self.assigned_scrolls.insert(name.to_string(), label);
+        }
+        self.assigned_scrolls[name].clone()
+    }
+
+    pub fn is_identified(&self, name: &str) -> bool {
+        self.identified.contains(name)
+    }
+}
+
+/// What the player knows an item as.
+pub fn display_name<U: GenericReadStorage<Component = Unidentified>>(item: Entity, names: &ReadStorage<Name>, unidentified: &U) -> String {
+    match unidentified.get(item) {
+        Some(disguise) => disguise.name.clone(),
+        None => names.get(item).map_or("something".to_string(), |name| name.name.clone())
+    }
+}
+
+/// Lets the player know everything called `name` by its real name: items already out there, what
+/// they remember seeing, and any spawned from now on.
+pub fn identify(name: &str, appearances: &mut ItemAppearances, entities: &Entities, names: &ReadStorage<Name>,
+    unidentified: &mut WriteStorage<Unidentified>, memory: &mut EntityMemory) {
+    appearances.identified.insert(name.to_string());
+
+    let disguised = (entities, names, &*unidentified).join()
+        .filter(|(_item, item_name, _disguise)| item_name.name == name)
+        .map(|(item, _item_name, _disguise)| item)
+        .collect::<Vec<_>>();
+    for item in disguised {
+        unidentified.remove(item);
+    }
+
+    let disguises = [appearances.assigned_potions.get(name).map(|look| look.name.clone()), appearances.assigned_scrolls.get(name).cloned()];
+    for remembered in memory.entities.values_mut() {
+        if remembered.name.is_some() && disguises.contains(&remembered.name) {
+            remembered.name = Some(name.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bracket_lib::prelude::RGB;
+    use crate::{register_components, RememberedEntity};
+
+    fn appearances(seed: u64) -> ItemAppearances {
+        ItemAppearances::new(&mut RandomNumberGenerator::seeded(seed))
+    }
+
+    fn remembered(name: Option<&str>) -> RememberedEntity {
+        RememberedEntity{ x: 1, y: 1, glyph: 0, foreground: RGB::named(BLUE), render_order: 2,
+            name: name.map(|name| name.to_string()), last_seen: 0 }
+    }
+
+    #[test]
+    fn each_kind_keeps_its_look() {
+        let mut appearances = appearances(1);
+        let healing = appearances.potion("Potion of Healing").name;
+        appearances.potion("Potion of Poison");
+        assert_eq!(appearances.potion("Potion of Healing").name, healing);
+
+        let fireball = appearances.scroll("Fireball Scroll");
+        appearances.scroll("Identify Scroll");
+        assert_eq!(appearances.scroll("Fireball Scroll"), fireball);
+    }
+
+    #[test]
+    fn different_kinds_look_different() {
+        let mut appearances = appearances(1);
+        let potions = (0..POTION_LOOKS.len() * POTION_COLOURS.len())
+            .map(|i| appearances.potion(&format!("Potion {}", i)).name)
+            .collect::<HashSet<_>>();
+        assert_eq!(potions.len(), POTION_LOOKS.len() * POTION_COLOURS.len());
+
+        let scrolls = (0..SCROLL_LABELS)
+            .map(|i| appearances.scroll(&format!("Scroll {}", i)))
+            .collect::<HashSet<_>>();
+        assert_eq!(scrolls.len(), SCROLL_LABELS);
+        assert!(scrolls.iter().all(|label| label.starts_with("scroll labelled ")));
+    }
+
+    #[test]
+    fn same_seed_same_looks() {
+        let (mut a, mut b) = (appearances(7), appearances(7));
+        for name in ["Potion of Healing", "Potion of Poison", "Potion of Telepathy"] {
+            assert_eq!(a.potion(name).name, b.potion(name).name);
+            assert_eq!(a.potion(name).colour, b.potion(name).colour);
+        }
+        assert_eq!(a.scroll("Fireball Scroll"), b.scroll("Fireball Scroll"));
+    }
+
+    #[test]
+    fn identify_reveals_every_item_of_the_kind() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        let mut appearances = appearances(1);
+        let healing_look = appearances.potion("Potion of Healing").name;
+        let poison_look = appearances.potion("Potion of Poison").name;
+
+        let mut item = |name: &str, look: &str| ecs.create_entity()
+            .with(Name{ name: name.to_string() })
+            .with(Unidentified{ name: look.to_string() })
+            .build();
+        let healing = [item("Potion of Healing", &healing_look), item("Potion of Healing", &healing_look)];
+        let poison = item("Potion of Poison", &poison_look);
+
+        let mut memory = EntityMemory::default();
+        memory.entities.insert(healing[0], remembered(Some(&healing_look)));
+        memory.entities.insert(poison, remembered(Some(&poison_look)));
+        let dagger = ecs.create_entity().build();
+        memory.entities.insert(dagger, remembered(None));
+
+        identify("Potion of Healing", &mut appearances, &ecs.entities(), &ecs.read_storage::<Name>(),
+            &mut ecs.write_storage::<Unidentified>(), &mut memory);
+
+        assert!(appearances.is_identified("Potion of Healing"));
+        assert!(!appearances.is_identified("Potion of Poison"));
+        let names = ecs.read_storage::<Name>();
+        let unidentified = ecs.read_storage::<Unidentified>();
+        for potion in healing {
+            assert_eq!(display_name(potion, &names, &unidentified), "Potion of Healing");
+        }
+        assert_eq!(display_name(poison, &names, &unidentified), poison_look);
+
+        assert_eq!(memory.entities[&healing[0]].name.as_deref(), Some("Potion of Healing"));
+        assert_eq!(memory.entities[&poison].name, Some(poison_look));
+        assert_eq!(memory.entities[&dagger].name, None);
+    }
+}
@@ -1,7 +1,12 @@
 use specs::prelude::*;
 
-use super::{WantsToPickupItem, Name, InBackpack, Position, gamelog::GameLog, WantsToDrinkPotion,
-    Potion, CombatStats, WantsToDropItem};
+use super::{WantsToPickupItem, Name, InBackpack, Position, gamelog::GameLog, WantsToUseItem,
+    Potion, Scroll, Consumable, ProvidesHealing, InflictsDamage, AreaOfEffect, WantsToAreaAttack, KnocksBack, Teleports, AreaShape, TileType, roll_damage, SufferDamage,
+    Viewshed, Awareness, AwarenessState, CombatStats, WantsToDropItem, GrantsTelepathy, GrantsItemDetection, Telepathic, DetectingItems,
+    GrantsMagicMapping, CausesAmnesia, GrantsStatus, StatusEffects, GameTurn, Map, RunState,
+    Equippable, Equipped, WantsToEquip, WantsToUnequip, Unidentified, Identifies, ItemAppearances, EntityMemory,
+    display_name, identify};
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
 
 pub struct ItemCollectionSystem {}
 
@@ -12,18 +17,19 @@ impl<'a> System<'a> for ItemCollectionSystem {
                         WriteStorage<'a, WantsToPickupItem>,
                         WriteStorage<'a, Position>,
                         ReadStorage<'a, Name>,
+                        ReadStorage<'a, Unidentified>,
                         WriteStorage<'a, InBackpack>
                     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) = data;
+        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, unidentified, mut backpack) = data;
 
         for pickup in wants_pickup.join() {
             positions.remove(pickup.item);
             backpack.insert(pickup.item, InBackpack{owner: pickup.collected_by}).expect("Unable to insert backpack entry");
 
             if pickup.collected_by == *player_entity {
-                gamelog.entries.push(format!("You pick up the {}.", names.get(pickup.item).unwrap().name));
+                gamelog.entries.push(format!("You pick up the {}.", display_name(pickup.item, &names, &unidentified)));
             }
         }
 
@@ -31,36 +37,204 @@ impl<'a> System<'a> for ItemCollectionSystem {
     }
 }
 
-pub struct PotionUseSystem {}
+/// Applies whatever effects an item has, to its user or to what it's aimed at, then uses it up
+/// if it's consumable. Each effect is a component on the item, so new kinds of item only need
+/// new combinations of them.
+pub struct ItemUseSystem {}
 
-impl<'a> System<'a> for PotionUseSystem {
+impl<'a> System<'a> for ItemUseSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, GameTurn>,
                         WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, Map>,
+                        WriteExpect<'a, Point>,
+                        WriteExpect<'a, RunState>,
+                        WriteExpect<'a, RandomNumberGenerator>,
                         Entities<'a>,
-                        WriteStorage<'a, WantsToDrinkPotion>,
+                        WriteStorage<'a, WantsToUseItem>,
                         ReadStorage<'a, Name>,
-                        ReadStorage<'a, Potion>,
-                        WriteStorage<'a, CombatStats>
+                        (ReadStorage<'a, Potion>, ReadStorage<'a, Scroll>),
+                        ReadStorage<'a, Consumable>,
+                        ReadStorage<'a, ProvidesHealing>,
+                        (ReadStorage<'a, InflictsDamage>, ReadStorage<'a, AreaOfEffect>, WriteStorage<'a, WantsToAreaAttack>, ReadStorage<'a, KnocksBack>),
+                        ReadStorage<'a, Teleports>,
+                        ReadStorage<'a, GrantsStatus>,
+                        (ReadStorage<'a, GrantsTelepathy>, ReadStorage<'a, GrantsItemDetection>, ReadStorage<'a, GrantsMagicMapping>, ReadStorage<'a, CausesAmnesia>),
+                        (WriteStorage<'a, Telepathic>, WriteStorage<'a, DetectingItems>),
+                        WriteStorage<'a, StatusEffects>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, Viewshed>,
+                        WriteStorage<'a, Awareness>,
+                        (WriteExpect<'a, ItemAppearances>, WriteExpect<'a, EntityMemory>, WriteStorage<'a, Unidentified>, ReadStorage<'a, Identifies>, ReadStorage<'a, InBackpack>)
                     );
+
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, entites, mut wants_drink, names, potions, mut combat_stats) = data;
-
-        for (entity, drink, stats) in (&entites, &wants_drink, &mut combat_stats).join() {
-            let potion = potions.get(drink.potion);
-            match potion {
-                None => {}
-                Some(potion) => {
-                    stats.hp = i32::min(stats.max_hp, stats.hp + potion.heal_amount);
-                    if entity == *player_entity {
-                        gamelog.entries.push(format!("You drink the {}, healing {} hp.", names.get(drink.potion).unwrap().name, potion.heal_amount));
+        let (player_entity, turn, mut gamelog, mut map, mut player_pos, mut runstate, mut rng, entities, mut wants_use, names,
+            (potions, scrolls), consumables, healing, (inflicts_damage, area_of_effect, mut wants_area, knocks_back), teleports, grants_status,
+            (grants_telepathy, grants_detection, magic_mapping, amnesia), (mut telepathic, mut detecting),
+            mut statuses, mut combat_stats, mut inflict_damage, mut positions, mut viewsheds, mut awareness,
+            (mut appearances, mut memory, mut unidentified, identifies, backpack)) = data;
+
+        for (entity, use_item) in (&entities, &wants_use).join() {
+            let item = use_item.item;
+            let is_player = entity == *player_entity;
+            let real_name = names.get(item).unwrap().name.clone();
+            let item_name = display_name(item, &names, &unidentified);
+            let user_pos = match positions.get(entity) {
+                Some(pos) => Point::new(pos.x, pos.y),
+                None => continue
+            };
+
+            // Everything it affects: what's around the target for area effects, what's on the
+            // target otherwise, and the user if it wasn't aimed anywhere
+            let centre = use_item.target.unwrap_or(user_pos);
+            let targets = match (area_of_effect.get(item), use_item.target) {
+                (Some(area), _) => map.entities_in_area(centre, AreaShape::Radius{ radius: area.radius }),
+                (None, Some(target)) => map.entities_at(map.xy_idx(target.x, target.y)).to_vec(),
+                (None, None) => vec![entity]
+            };
+            let targets = targets.into_iter().filter(|target| combat_stats.contains(*target)).collect::<Vec<_>>();
+            let describe = |target: Entity| if target == *player_entity { "You".to_string() } else { names.get(target).map_or("It".to_string(), |n| n.name.clone()) };
+
+            let mut effects: Vec<String> = Vec::new();
+
+            if let Some(heal) = healing.get(item) {
+                for target in targets.iter() {
+                    if let Some(stats) = combat_stats.get_mut(*target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + heal.heal_amount);
+                        effects.push(format!("{} {} {} hp.", describe(*target), if *target == *player_entity { "heal" } else { "heals" }, heal.heal_amount));
+                    }
+                }
+            }
+
+            // Blasts are handed on to the area attack system, like any other explosion
+            if let (Some(damage), Some(area)) = (inflicts_damage.get(item), area_of_effect.get(item)) {
+                wants_area.insert(entity, WantsToAreaAttack{
+                    origin: centre,
+                    shape: AreaShape::Radius{ radius: area.radius },
+                    n_dice: damage.n_dice, die_type: damage.die_type, bonus: 0, kind: damage.kind,
+                    knockback: knocks_back.get(item).map_or(0, |push| push.distance)
+                }).expect("Unable to insert area attack");
+                effects.push(format!("A blast of {} bursts out!", damage.kind.name()));
+            } else if let Some(damage) = inflicts_damage.get(item) {
+                let amount = roll_damage(&mut rng, damage.n_dice, damage.die_type, 0, false);
+                for target in targets.iter() {
+                    SufferDamage::new_damage(&mut inflict_damage, *target, amount, damage.kind);
+                    effects.push(format!("{} {} {} {} damage.", describe(*target), if *target == *player_entity { "take" } else { "takes" },
+                        amount, damage.kind.name()));
+                    if *target != entity {
+                        if let Some(aware) = awareness.get_mut(*target) {
+                            aware.state = AwarenessState::Hunting{ target: entity, last_seen: user_pos };
+                        }
                     }
-                    entites.delete(drink.potion).expect("Delete failed");
                 }
             }
+
+            if let Some(grant) = grants_status.get(item) {
+                for target in targets.iter() {
+                    StatusEffects::add(&mut statuses, *target, grant.kind, grant.turns);
+                    if *target != entity {
+                        if let Some(aware) = awareness.get_mut(*target) {
+                            aware.state = AwarenessState::Hunting{ target: entity, last_seen: user_pos };
+                        }
+                    }
+                    let state = grant.kind.name().to_lowercase();
+                    if *target == *player_entity {
+                        effects.push(format!("You are {}!", state));
+                    } else {
+                        effects.push(format!("{} is {}.", describe(*target), state));
+                    }
+                }
+            }
+
+            if teleports.contains(item) {
+                let floor = (0..map.tiles.len())
+                    .filter(|idx| map.tiles[*idx] == TileType::Floor && !map.blocked[*idx])
+                    .collect::<Vec<_>>();
+                for target in targets.iter() {
+                    if floor.is_empty() { break; }
+                    let idx = floor[rng.range(0, floor.len() as i32) as usize];
+                    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+                    if let Some(pos) = positions.get_mut(*target) {
+                        pos.x = x;
+                        pos.y = y;
+                        map.move_entity(*target, idx);
+                    }
+                    if let Some(viewshed) = viewsheds.get_mut(*target) {
+                        viewshed.dirty = true;
+                    }
+                    if *target == *player_entity {
+                        *player_pos = Point::new(x, y);
+                        effects.push("The world lurches around you!".to_string());
+                    } else {
+                        effects.push(format!("{} vanishes!", describe(*target)));
+                    }
+                }
+            }
+
+            if let Some(grant) = grants_telepathy.get(item) {
+                telepathic.insert(entity, Telepathic{ range: grant.range, expires: turn.number + grant.duration })
+                    .expect("Unable to insert telepathy");
+                effects.push("You feel minds stirring around you.".to_string());
+            }
+
+            if let Some(grant) = grants_detection.get(item) {
+                detecting.insert(entity, DetectingItems{ range: grant.range, expires: turn.number + grant.duration })
+                    .expect("Unable to insert item detection");
+                effects.push("You sense the objects around you.".to_string());
+            }
+
+            // Map knowledge is the player's alone, so these do nothing for anyone else
+            if is_player && magic_mapping.contains(item) {
+                *runstate = RunState::MagicMapReveal{ row: 0 };
+                effects.push("The level unfolds in your mind!".to_string());
+            }
+
+            if let Some(forget) = amnesia.get(item) {
+                if is_player {
+                    map.forget_beyond(*player_pos, forget.radius);
+                    effects.push("Wait, where are you?".to_string());
+                }
+            }
+
+            if identifies.contains(item) {
+                let unknown = (&entities, &backpack, &names, &unidentified).join()
+                    .filter(|(_other, pack, other_name, _disguise)| pack.owner == entity && other_name.name != real_name)
+                    .map(|(_other, _pack, other_name, disguise)| (other_name.name.clone(), disguise.name.clone()))
+                    .collect::<Vec<_>>();
+                if unknown.is_empty() {
+                    effects.push("You learn nothing new.".to_string());
+                } else {
+                    let (name, disguise) = unknown[rng.range(0, unknown.len() as i32) as usize].clone();
+                    identify(&name, &mut appearances, &entities, &names, &mut unidentified, &mut memory);
+                    effects.push(format!("The {} turns out to be a {}.", disguise, name));
+                }
+            }
+
+            // Using something is the surest way to find out what it is
+            if is_player && unidentified.contains(item) {
+                identify(&real_name, &mut appearances, &entities, &names, &mut unidentified, &mut memory);
+                effects.push(format!("It was a {}.", real_name));
+            }
+
+            if is_player {
+                let verb = if potions.contains(item) { "drink" } else if scrolls.contains(item) { "read" } else { "use" };
+                if effects.is_empty() && !consumables.contains(item) {
+                    gamelog.entries.push(format!("You can't think of a way to use the {}.", item_name));
+                } else {
+                    gamelog.entries.push(format!("You {} the {}. {}", verb, item_name, effects.join(" ")).trim_end().to_string());
+                }
+            }
+
+            if consumables.contains(item) {
+                entities.delete(item).expect("Delete failed");
+            }
         }
 
-        wants_drink.clear();
+        wants_use.clear();
     }
 }
 
@@ -73,11 +247,13 @@ impl<'a> System<'a> for ItemDropSystem {
                         Entities<'a>,
                         WriteStorage<'a, WantsToDropItem>,
                         ReadStorage<'a, Name>,
+                        ReadStorage<'a, Unidentified>,
                         WriteStorage<'a, Position>,
-                        WriteStorage<'a, InBackpack>
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Equipped>
                     );
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, entities, mut wants_drop, names, mut positions, mut backpack) = data;
+        let (player_entity, mut gamelog, entities, mut wants_drop, names, unidentified, mut positions, mut backpack, mut equipped) = data;
 
         for (entity, to_drop) in (&entities, &wants_drop).join() {
             let mut dropper_pos: Position = Position{x:0, y:0};
@@ -90,12 +266,83 @@ impl<'a> System<'a> for ItemDropSystem {
 
             positions.insert(to_drop.item, Position{x: dropper_pos.x, y: dropper_pos.y}).expect("Unable to insert position");
             backpack.remove(to_drop.item);
+            equipped.remove(to_drop.item);
 
             if entity == *player_entity {
-                gamelog.entries.push(format!("You drop the {}.", names.get(to_drop.item).unwrap().name));
+                gamelog.entries.push(format!("You drop the {}.", display_name(to_drop.item, &names, &unidentified)));
             }
         }
 
         wants_drop.clear();
     }
 }
+
+/// Puts items on, taking off whatever was already in their slot.
+pub struct ItemEquipSystem {}
+
+impl<'a> System<'a> for ItemEquipSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToEquip>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Equippable>,
+                        WriteStorage<'a, Equipped>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut gamelog, entities, mut wants_equip, names, equippable, mut equipped) = data;
+
+        for (entity, equip) in (&entities, &wants_equip).join() {
+            let slot = match equippable.get(equip.item) {
+                Some(equippable) => equippable.slot,
+                None => continue
+            };
+            let is_player = entity == *player_entity;
+
+            let replaced = (&entities, &equipped).join()
+                .filter(|(_item, worn)| worn.owner == entity && worn.slot == slot)
+                .map(|(item, _worn)| item)
+                .collect::<Vec<_>>();
+            for item in replaced {
+                equipped.remove(item);
+                if is_player {
+                    gamelog.entries.push(format!("You take off the {}.", names.get(item).unwrap().name));
+                }
+            }
+
+            equipped.insert(equip.item, Equipped{ owner: entity, slot }).expect("Unable to insert equipped");
+            if is_player {
+                gamelog.entries.push(format!("You equip the {}.", names.get(equip.item).unwrap().name));
+            }
+        }
+
+        wants_equip.clear();
+    }
+}
+
+pub struct ItemUnequipSystem {}
+
+impl<'a> System<'a> for ItemUnequipSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToUnequip>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Equipped>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut gamelog, entities, mut wants_unequip, names, mut equipped) = data;
+
+        for (entity, unequip) in (&entities, &wants_unequip).join() {
+            if equipped.remove(unequip.item).is_some() && entity == *player_entity {
+                gamelog.entries.push(format!("You take off the {}.", names.get(unequip.item).unwrap().name));
+            }
+        }
+
+        wants_unequip.clear();
+    }
+}
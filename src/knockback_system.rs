@@ -0,0 +1,76 @@
+use specs::prelude::*;
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+use super::{Knockback, Position, Viewshed, CombatStats, Name, SufferDamage, DamageType, Map, gamelog::GameLog};
+
+/// Damage die for slamming into something solid.
+const COLLISION_DIE: i32 = 6;
+
+/// Shoves knocked-back creatures away from the blow a tile at a time. Running into a wall or
+/// another creature ends the push, and the collision hurts both.
+pub struct KnockbackSystem {}
+
+impl<'a> System<'a> for KnockbackSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, Point>,
+                        WriteExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, Knockback>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, Viewshed>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SufferDamage>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut player_pos, mut map, mut rng, mut log, entities, mut knockback, mut positions,
+            mut viewsheds, combat_stats, names, mut inflict_damage) = data;
+
+        for (entity, push, pos) in (&entities, &knockback, &mut positions).join() {
+            let (dx, dy) = ((pos.x - push.from.x).signum(), (pos.y - push.from.y).signum());
+            if dx == 0 && dy == 0 { continue; }
+            let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+
+            for _ in 0..push.distance {
+                let (x, y) = (pos.x + dx, pos.y + dy);
+                let next = (x >= 0 && x < map.width && y >= 0 && y < map.height).then(|| map.xy_idx(x, y));
+
+                // The player doesn't block tiles, so look for creatures as well
+                let obstacle = next.and_then(|idx| map.entities_at(idx).iter()
+                    .find(|other| **other != entity && combat_stats.contains(**other)).copied());
+                let idx = match next {
+                    Some(idx) if !map.blocked[idx] && obstacle.is_none() => idx,
+                    _ => {
+                        let damage = rng.roll_dice(1, COLLISION_DIE);
+                        SufferDamage::new_damage(&mut inflict_damage, entity, damage, DamageType::Bludgeoning);
+                        match obstacle {
+                            Some(obstacle) => {
+                                SufferDamage::new_damage(&mut inflict_damage, obstacle, damage, DamageType::Bludgeoning);
+                                let other = names.get(obstacle).map_or("something", |n| n.name.as_str());
+                                log.entries.push(format!("{} slams into {}, for {} hp!", name, other, damage));
+                            }
+                            None => log.entries.push(format!("{} slams into the wall, for {} hp!", name, damage))
+                        }
+                        break;
+                    }
+                };
+
+                pos.x = x;
+                pos.y = y;
+                map.move_entity(entity, idx);
+                if let Some(viewshed) = viewsheds.get_mut(entity) {
+                    viewshed.dirty = true;
+                }
+                if entity == *player_entity {
+                    player_pos.x = x;
+                    player_pos.y = y;
+                }
+            }
+        }
+
+        knockback.clear();
+    }
+}
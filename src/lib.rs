@@ -0,0 +1,61 @@
+mod components;
+pub use components::*;
+
+mod map;
+pub use map::*;
+mod monster_ai_system;
+pub use monster_ai_system::*;
+mod player;
+pub use player::*;
+mod rect;
+pub use rect::Rect;
+mod room;
+pub use room::{Room, RoomShape, RoomTheme};
+mod corridors;
+pub use corridors::{CorridorSettings, CorridorStrategy, CorridorStyle, plan_connections};
+
+mod fov;
+pub use fov::{symmetric_fov, in_fov_range};
+mod visibility_system;
+pub use visibility_system::{VisibilitySystem, SensingSystem};
+mod map_indexing_system;
+pub use map_indexing_system::*;
+mod damage_system;
+pub use damage_system::*;
+mod faction;
+pub use faction::{Faction, Reaction, Reactions};
+mod combat;
+pub use combat::{AttackRoll, roll_attack, roll_damage, power_bonus, defense_bonus, wielded_damage};
+mod melee_combat_system;
+pub use melee_combat_system::MeleeCombatSystem;
+mod ranged_combat_system;
+pub use ranged_combat_system::{RangedCombatSystem, ranged_weapon_of, ammunition_of};
+mod gui;
+pub use gui::*;
+pub mod gamelog;
+pub use gamelog::*;
+pub mod spawner;
+pub use spawner::*;
+mod inventory_system;
+pub use inventory_system::*;
+mod identification;
+pub use identification::{ItemAppearances, PotionLook, display_name, identify};
+mod ability_system;
+pub use ability_system::AbilitySystem;
+mod area_effect_system;
+pub use area_effect_system::AreaAttackSystem;
+mod knockback_system;
+pub use knockback_system::KnockbackSystem;
+mod memory_system;
+pub use memory_system::*;
+mod status_effect_system;
+pub use status_effect_system::*;
+mod regeneration_system;
+pub use regeneration_system::RegenerationSystem;
+mod corpse_system;
+pub use corpse_system::*;
+mod noise_system;
+pub use noise_system::*;
+
+mod state;
+pub use state::{State, RunState, GameTurn};
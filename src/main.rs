@@ -1,40 +1,39 @@
-use bracket_lib::prelude::{BError, RGB, RandomNumberGenerator, Point};
+use bracket_lib::prelude::{BError, RandomNumberGenerator, Point};
 use specs::prelude::*;
 
-mod components;
-pub use components::*;
+use roguelike::*;
 
-mod map;
-pub use map::*;
-mod monster_ai_system;
-pub use monster_ai_system::*;
-mod player;
-pub use player::*;
-mod rect;
-pub use rect::Rect;
-
-mod visibility_system;
-pub use visibility_system::VisibilitySystem;
-mod map_indexing_system;
-pub use map_indexing_system::*;
-mod damage_system;
-pub use damage_system::*;
-mod melee_combat_system;
-mod gui;
-pub use gui::*;
-mod gamelog;
-pub use gamelog::*;
-mod spawner;
-pub use spawner::*;
-mod inventory_system;
-pub use inventory_system::*;
-
-mod state;
-pub use state::{State, RunState};
+/// Corridor settings picked on the command line, with anything left out chosen at random:
+///
+/// roguelike [--corridors sequential|nearest|mst[:N]|bsp] [--corridor-style dogleg|drunkard] [--corridor-width N]
+fn corridor_settings(rng: &mut RandomNumberGenerator) -> CorridorSettings {
+    let mut settings = CorridorSettings::random(rng);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--corridors" => args.next().ok_or("--corridors needs a strategy".to_string())
+                .and_then(|s| s.parse()).map(|strategy| settings.strategy = strategy),
+            "--corridor-style" => args.next().ok_or("--corridor-style needs a style".to_string())
+                .and_then(|s| s.parse()).map(|style| settings.style = style),
+            "--corridor-width" => args.next().and_then(|n| n.parse().ok()).filter(|width| *width >= 1)
+                .ok_or("--corridor-width needs a positive number".to_string()).map(|width| settings.width = width),
+            _ => Err(format!("unknown argument '{}'", arg))
+        };
+        if let Err(error) = parsed {
+            eprintln!("{}", error);
+            eprintln!("usage: roguelike [--corridors sequential|nearest|mst[:N]|bsp] [--corridor-style dogleg|drunkard] [--corridor-width N]");
+            std::process::exit(2);
+        }
+    }
+    settings
+}
 
 fn main() -> BError {
     use bracket_lib::prelude::BTermBuilder;
 
+    let mut rng = RandomNumberGenerator::new();
+    let corridors = corridor_settings(&mut rng);
+
     let context = BTermBuilder::simple80x50()
         .with_title("Roguelike Tutorial")
         .build()?;
@@ -44,38 +43,36 @@ fn main() -> BError {
         ecs: World::new()
     };
 
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<Name>();
-    gs.ecs.register::<BlocksTile>();
-    gs.ecs.register::<CombatStats>();
-    gs.ecs.register::<WantsToMelee>();
-    gs.ecs.register::<SufferDamage>();
-    gs.ecs.register::<Item>();
-    gs.ecs.register::<Potion>();
-    gs.ecs.register::<InBackpack>();
-    gs.ecs.register::<WantsToPickupItem>();
-    gs.ecs.register::<WantsToDrinkPotion>();
-    gs.ecs.register::<WantsToDropItem>();
+    register_components(&mut gs.ecs);
+
+    // Start listening for position changes before anything is spawned, so the spatial index
+    // sees every entity.
+    let position_reader = gs.ecs.write_storage::<Position>().register_reader();
+    gs.ecs.insert(PositionReader{ reader: position_reader });
 
-    let map: Map = Map::new_map_rooms_and_corridors();
+    let map: Map = Map::new_map_rooms_and_corridors(corridors);
     let (player_x, player_y) = map.rooms[0].center();
+    gs.ecs.insert(ItemAppearances::new(&mut rng));
 
     let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
 
-    gs.ecs.insert(RandomNumberGenerator::new());
+    gs.ecs.insert(rng);
     for room in map.rooms.iter().skip(1) {
         spawner::spawn_room(&mut gs.ecs, room);
     }
 
+    let mut log_entries = vec!["Welcome to the Rust powered Roguelike".to_string()];
+    log_entries.extend(map.level_feelings());
+
     gs.ecs.insert(map);
     gs.ecs.insert(Point::new(player_x, player_y));
     gs.ecs.insert(player_entity);
     gs.ecs.insert(RunState::PreRun);
-    gs.ecs.insert(gamelog::GameLog{ entries: vec!["Welcome to the Rust powered Roguelike".to_string()]});
+    gs.ecs.insert(GameTurn{ number: 0 });
+    gs.ecs.insert(EntityMemory::default());
+    gs.ecs.insert(NoiseEvents::default());
+    gs.ecs.insert(Reactions::default());
+    gs.ecs.insert(gamelog::GameLog{ entries: log_entries });
     
     
     bracket_lib::prelude::main_loop(context, gs)
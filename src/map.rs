@@ -1,7 +1,9 @@
-use bracket_lib::prelude::{ Algorithm2D, BTerm, BaseMap, Point, RandomNumberGenerator, RGB, SmallVec };
+use bracket_lib::prelude::{ Algorithm2D, BTerm, BaseMap, line2d_bresenham, Point, RandomNumberGenerator, RGB, SmallVec };
 use std::cmp::{max, min};
-use super::Rect;
+use std::collections::{HashMap, HashSet};
+use super::{symmetric_fov, Rect, Room, RoomShape, RoomTheme, CorridorSettings, CorridorStyle, plan_connections};
 use specs::prelude::*;
+use specs::world::Index;
 
 pub const MAPWIDTH: usize = 80;
 pub const MAPHEIGHT: usize = 50;
@@ -10,24 +12,52 @@ pub const MAPCOUNT: usize = MAPHEIGHT * MAPWIDTH;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum TileType {
-    Wall, Floor
+    Wall, Floor, Door,
+    /// Looks and behaves like a wall until the player finds it by searching.
+    SecretDoor
 }
 
+/// The area an explosion or breath fills.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AreaShape {
+    Radius { radius: i32 },
+    /// Spreads out from the origin towards `towards`, up to `half_angle` degrees either side.
+    Cone { towards: Point, radius: i32, half_angle: f32 }
+}
+
+/// Where an entity currently sits in the spatial index, and whether it blocks that tile.
+#[derive(Copy, Clone)]
+struct IndexedEntity {
+    idx: usize,
+    blocks: bool
+}
 
 pub struct Map {
     pub tiles: Vec<TileType>,
-    pub rooms: Vec<Rect>,
+    pub rooms: Vec<Room>,
     pub width: i32,
     pub height: i32,
     pub revealed_tiles: Vec<bool>,
     pub visible_tiles: Vec<bool>,
+    /// Creatures and items the player is aware of through telepathy or detection, without being
+    /// able to see them. Only these are shown; whatever else shares their tiles stays hidden.
+    pub sensed: HashSet<Entity>,
     pub blocked: Vec<bool>,
-    pub tile_content: Vec<Vec<Entity>>
+    pub tile_content: Vec<Vec<Entity>>,
+    bloodstains: HashSet<usize>,
+    indexed: HashMap<Index, IndexedEntity>,
+    opaque: Vec<bool>,
+    /// Fields of view already worked out, by origin tile and range. Only valid while `opaque`
+    /// stays the same.
+    fov_cache: HashMap<(usize, i32), Vec<Point>>
 }
 
+/// How many fields of view to remember before starting again, to keep huge maps in check.
+const FOV_CACHE_LIMIT: usize = 20_000;
+
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        self.opaque[idx]
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
@@ -66,54 +96,314 @@ impl Algorithm2D for Map {
 }
 
 impl Map {
+    /// A map of solid wall. Call `populate_blocked` once the tiles have been carved out.
+    pub fn new(width: i32, height: i32) -> Map {
+        let count = (width * height) as usize;
+        Map {
+            tiles: vec![TileType::Wall; count],
+            rooms: Vec::new(),
+            width,
+            height,
+            revealed_tiles: vec![false; count],
+            visible_tiles: vec![false; count],
+            sensed: HashSet::new(),
+            blocked: vec![false; count],
+            tile_content: vec![Vec::new(); count],
+            bloodstains: HashSet::new(),
+            indexed: HashMap::new(),
+            opaque: vec![true; count],
+            fov_cache: HashMap::new()
+        }
+    }
+
     pub fn xy_idx(&self, x: i32, y: i32) -> usize {
         (y as usize * self.width as usize) + x as usize
     }
 
-    pub fn clear_content_index(&mut self) {
-        for content in self.tile_content.iter_mut() {
-            content.clear();
+    /// Adds an entity to the spatial index at `idx`, moving it there if it is already indexed.
+    pub fn index_entity(&mut self, entity: Entity, idx: usize, blocks: bool) {
+        if let Some(current) = self.indexed.get(&entity.id()) {
+            if current.idx == idx && current.blocks == blocks { return; }
+            self.unindex_entity(entity.id());
+        }
+
+        self.indexed.insert(entity.id(), IndexedEntity{ idx, blocks });
+        self.tile_content[idx].push(entity);
+        if blocks {
+            self.blocked[idx] = true;
         }
     }
 
-    fn apply_horizontal_tunnel(&mut self, x1:i32, x2: i32, y: i32) {
-        for x in min(x1, x2) ..= max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize { // map size
-                self.tiles[idx as usize] = TileType::Floor;
+    /// Moves an indexed entity to `idx`, keeping its blocking flag. Unindexed entities are
+    /// ignored; they will be picked up when their `Position` insert event is processed.
+    pub fn move_entity(&mut self, entity: Entity, idx: usize) {
+        if let Some(current) = self.indexed.get(&entity.id()) {
+            let blocks = current.blocks;
+            self.index_entity(entity, idx, blocks);
+        }
+    }
+
+    /// Removes an entity from the spatial index by its specs index, which is all we get once
+    /// its `Position` has been removed.
+    pub fn unindex_entity(&mut self, id: Index) {
+        if let Some(old) = self.indexed.remove(&id) {
+            self.tile_content[old.idx].retain(|e| e.id() != id);
+            if old.blocks {
+                self.refresh_blocked(old.idx);
+            }
+        }
+    }
+
+    pub fn entities_at(&self, idx: usize) -> &[Entity] {
+        &self.tile_content[idx]
+    }
+
+    /// Every indexed entity within `radius` tiles of `center`. Only the tiles in range are
+    /// visited, so the cost does not depend on how many entities are on the map.
+    pub fn entities_in_radius(&self, center: Point, radius: i32) -> Vec<Entity> {
+        let mut result = Vec::new();
+        for y in max(0, center.y - radius) ..= min(self.height - 1, center.y + radius) {
+            for x in max(0, center.x - radius) ..= min(self.width - 1, center.x + radius) {
+                let distance = bracket_lib::geometry::DistanceAlg::Pythagoras.distance2d(center, Point::new(x, y));
+                if distance <= radius as f32 {
+                    result.extend_from_slice(&self.tile_content[self.xy_idx(x, y)]);
+                }
+            }
+        }
+        result
+    }
+
+    /// Every tile an effect of `shape` centred on `origin` reaches, which is those in the shape
+    /// that `origin` has line of sight to.
+    pub fn area_tiles(&mut self, origin: Point, shape: AreaShape) -> Vec<Point> {
+        match shape {
+            AreaShape::Radius{ radius } => self.field_of_view(origin, radius),
+            AreaShape::Cone{ towards, radius, half_angle } => {
+                let (aim_x, aim_y) = ((towards.x - origin.x) as f32, (towards.y - origin.y) as f32);
+                let aim_length = (aim_x * aim_x + aim_y * aim_y).sqrt();
+                if aim_length == 0.0 { return Vec::new(); }
+                let min_cos = half_angle.to_radians().cos();
+
+                self.field_of_view(origin, radius).into_iter()
+                    .filter(|tile| {
+                        let (x, y) = ((tile.x - origin.x) as f32, (tile.y - origin.y) as f32);
+                        let length = (x * x + y * y).sqrt();
+                        length > 0.0 && (x * aim_x + y * aim_y) / (length * aim_length) >= min_cos
+                    })
+                    .collect()
             }
         }
     }
+
+    /// Everything standing in the tiles an effect of `shape` centred on `origin` reaches.
+    pub fn entities_in_area(&mut self, origin: Point, shape: AreaShape) -> Vec<Entity> {
+        let mut result = Vec::new();
+        for tile in self.area_tiles(origin, shape) {
+            result.extend_from_slice(&self.tile_content[self.xy_idx(tile.x, tile.y)]);
+        }
+        result
+    }
+
+    fn refresh_blocked(&mut self, idx: usize) {
+        let indexed = &self.indexed;
+        self.blocked[idx] = matches!(self.tiles[idx], TileType::Wall | TileType::SecretDoor)
+            || self.tile_content[idx].iter().any(|e| indexed.get(&e.id()).is_some_and(|i| i.blocks));
+    }
+
+    /// Digs out a `width` by `width` block with its top-left corner at (x, y), leaving the
+    /// outer edge of the map alone.
+    fn apply_corridor_tile(&mut self, x: i32, y: i32, width: i32) {
+        for ty in y .. y + width {
+            for tx in x .. x + width {
+                if tx > 0 && tx < self.width - 1 && ty > 0 && ty < self.height - 1 {
+                    let idx = self.xy_idx(tx, ty);
+                    self.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+    }
+
+    fn apply_horizontal_tunnel(&mut self, x1:i32, x2: i32, y: i32, width: i32) {
+        for x in min(x1, x2) ..= max(x1, x2) {
+            self.apply_corridor_tile(x, y, width);
+        }
+    }
     
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
+    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32, width: i32) {
         for y in min(y1, y2) ..= max(y1, y2) {
+            self.apply_corridor_tile(x, y, width);
+        }
+    }
+
+    fn apply_dogleg_tunnel(&mut self, from: (i32, i32), to: (i32, i32), width: i32, rng: &mut RandomNumberGenerator) {
+        if rng.range(0,2) == 1 {
+            self.apply_horizontal_tunnel(from.0, to.0, from.1, width);
+            self.apply_vertical_tunnel(from.1, to.1, to.0, width);
+        } else {
+            self.apply_vertical_tunnel(from.1, to.1, from.0, width);
+            self.apply_horizontal_tunnel(from.0, to.0, to.1, width);
+        }
+    }
+
+    /// Staggers from one point towards another, mostly stepping closer but sometimes wandering
+    /// off to the side. If it takes too long the rest of the way is dug as a dogleg.
+    fn apply_drunkard_tunnel(&mut self, from: (i32, i32), to: (i32, i32), width: i32, rng: &mut RandomNumberGenerator) {
+        const MAX_STEPS: i32 = 1000;
+
+        let (mut x, mut y) = from;
+        self.apply_corridor_tile(x, y, width);
+
+        for _ in 0..MAX_STEPS {
+            if (x, y) == to { return; }
+
+            let (dx, dy) = ((to.0 - x).signum(), (to.1 - y).signum());
+            if rng.roll_dice(1, 10) <= 6 {
+                if dx != 0 && (dy == 0 || rng.range(0, 2) == 0) { x += dx; } else { y += dy; }
+            } else {
+                match rng.roll_dice(1, 4) {
+                    1 => x -= 1,
+                    2 => x += 1,
+                    3 => y -= 1,
+                    _ => y += 1
+                }
+            }
+            x = x.clamp(1, self.width - 2);
+            y = y.clamp(1, self.height - 2);
+            self.apply_corridor_tile(x, y, width);
+        }
+
+        self.apply_dogleg_tunnel((x, y), to, width, rng);
+    }
+
+    fn apply_corridor(&mut self, from: (i32, i32), to: (i32, i32), settings: &CorridorSettings, rng: &mut RandomNumberGenerator) {
+        match settings.style {
+            CorridorStyle::Dogleg => self.apply_dogleg_tunnel(from, to, settings.width, rng),
+            CorridorStyle::Drunkard => self.apply_drunkard_tunnel(from, to, settings.width, rng)
+        }
+    }
+
+    /// Marks a tile as seen, so it stays on the map once out of view.
+    pub fn reveal_tile(&mut self, idx: usize) {
+        self.revealed_tiles[idx] = true;
+    }
+
+    /// Reveals the layout on row `y`, for magic mapping to sweep down the level a row at a time:
+    /// floors, doors and any wall touching a floor. Solid rock stays hidden. Secret doors in
+    /// those walls are revealed too, but they still look like walls.
+    pub fn reveal_row(&mut self, y: i32) {
+        if y < 0 || y >= self.height { return; }
+        for x in 0..self.width {
             let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = TileType::Floor;
+            if self.is_part_of_layout(x, y) {
+                self.revealed_tiles[idx] = true;
             }
         }
     }
 
-    fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 +1 ..= room.y2 {
-            for x in room.x1 + 1 ..= room.x2 {
-                let idx = self.xy_idx(x, y);
-                self.tiles[idx] = TileType::Floor;
+    fn is_part_of_layout(&self, x: i32, y: i32) -> bool {
+        match self.tiles[self.xy_idx(x, y)] {
+            TileType::Floor | TileType::Door => true,
+            TileType::Wall | TileType::SecretDoor => {
+                (max(0, y - 1) ..= min(self.height - 1, y + 1))
+                    .flat_map(|ny| (max(0, x - 1) ..= min(self.width - 1, x + 1)).map(move |nx| (nx, ny)))
+                    .any(|(nx, ny)| self.tiles[self.xy_idx(nx, ny)] == TileType::Floor)
             }
         }
-    } 
-    
-    pub fn new_map_rooms_and_corridors() -> Map {
-        let mut map = Map {
-            tiles: vec![TileType::Wall; MAPCOUNT],
-            rooms: Vec::new(),
-            width: 80,
-            height: 40,
-            revealed_tiles: vec![false; MAPCOUNT],
-            visible_tiles: vec![false; MAPCOUNT],
-            blocked: vec![false; MAPCOUNT],
-            tile_content: vec![Vec::new(); MAPCOUNT]
+    }
+
+    /// Forgets every revealed tile further than `radius` from `center`, apart from those in view.
+    /// Returns how many tiles were forgotten.
+    pub fn forget_beyond(&mut self, center: Point, radius: i32) -> usize {
+        let mut forgotten = 0;
+        for idx in 0..self.revealed_tiles.len() {
+            if !self.revealed_tiles[idx] || self.visible_tiles[idx] { continue; }
+            let (x, y) = (idx as i32 % self.width, idx as i32 / self.width);
+            if (x - center.x) * (x - center.x) + (y - center.y) * (y - center.y) > radius * radius {
+                self.revealed_tiles[idx] = false;
+                forgotten += 1;
+            }
+        }
+        forgotten
+    }
+
+    pub fn add_bloodstain(&mut self, idx: usize) {
+        self.bloodstains.insert(idx);
+    }
+
+    pub fn is_bloodstained(&self, idx: usize) -> bool {
+        self.bloodstains.contains(&idx)
+    }
+
+    /// Turns a secret door into a regular door and marks it as seen.
+    pub fn discover_secret_door(&mut self, idx: usize) {
+        if self.tiles[idx] != TileType::SecretDoor { return; }
+
+        self.tiles[idx] = TileType::Door;
+        self.reveal_tile(idx);
+        self.refresh_blocked(idx);
+        self.refresh_opacity(idx);
+    }
+
+    /// Tries to tuck a small vault against one side of a room (never the starting room). The
+    /// vault only shares a single wall tile with the room, which becomes a secret door, and it
+    /// must be carved out of solid rock so no corridor leads in some other way.
+    fn try_add_vault(&mut self, rng: &mut RandomNumberGenerator) -> Option<Room> {
+        let candidates: Vec<&Room> = self.rooms.iter()
+            .skip(1)
+            .filter(|room| room.theme != RoomTheme::TreasureVault)
+            .collect();
+        if candidates.is_empty() { return None; }
+
+        let room = candidates[rng.range(0, candidates.len() as i32) as usize];
+        let r = room.rect;
+        let size = rng.range(3, 5);
+        // The vault, the secret door, and the room floor tile just inside the door
+        let (vault, door_x, door_y, inside) = match rng.range(0, 4) {
+            0 => {
+                let y = rng.range(r.y1 + 1, r.y2 + 1);
+                (Rect::new(r.x2 + 1, y - size / 2, size, size), r.x2 + 1, y, (r.x2, y))
+            }
+            1 => {
+                let y = rng.range(r.y1 + 1, r.y2 + 1);
+                (Rect::new(r.x1 - size - 1, y - size / 2, size, size), r.x1, y, (r.x1 + 1, y))
+            }
+            2 => {
+                let x = rng.range(r.x1 + 1, r.x2 + 1);
+                (Rect::new(x - size / 2, r.y2 + 1, size, size), x, r.y2 + 1, (x, r.y2))
+            }
+            _ => {
+                let x = rng.range(r.x1 + 1, r.x2 + 1);
+                (Rect::new(x - size / 2, r.y1 - size - 1, size, size), x, r.y1, (x, r.y1 + 1))
+            }
         };
+
+        // Shaped rooms don't fill their bounding box, so make sure the door opens onto floor
+        if !room.contains(inside.0, inside.1) { return None; }
+        if vault.x1 < 0 || vault.y1 < 0 || vault.x2 + 1 >= self.width || vault.y2 + 1 >= self.height {
+            return None;
+        }
+        for y in vault.y1 ..= vault.y2 + 1 {
+            for x in vault.x1 ..= vault.x2 + 1 {
+                if self.tiles[self.xy_idx(x, y)] != TileType::Wall { return None; }
+            }
+        }
+
+        let vault = Room::new(vault, RoomShape::Rectangle, RoomTheme::TreasureVault, rng);
+        self.apply_room_to_map(&vault);
+        let door_idx = self.xy_idx(door_x, door_y);
+        self.tiles[door_idx] = TileType::SecretDoor;
+        Some(vault)
+    }
+
+    fn apply_room_to_map(&mut self, room: &Room) {
+        for (x, y) in room.floor_tiles().iter() {
+            let idx = self.xy_idx(*x, *y);
+            self.tiles[idx] = TileType::Floor;
+        }
+    }
+    
+    pub fn new_map_rooms_and_corridors(corridors: CorridorSettings) -> Map {
+        let mut map = Map::new(80, 40);
         
         const MAX_ROOMS: i32 = 30;
         const MIN_SIZE: i32 = 6;
@@ -131,30 +421,44 @@ impl Map {
             let mut ok = true;
     
             for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) {
+                if other_room.intersect(&new_room) {
                     ok = false
                 }
             }
             if ok {
+                // The first room is where the player starts, so keep it plain
+                let (shape, theme) = if map.rooms.is_empty() {
+                    (RoomShape::Rectangle, RoomTheme::Ordinary)
+                } else {
+                    (RoomShape::roll(&mut rng), RoomTheme::roll(&mut rng))
+                };
+                let new_room = Room::new(new_room, shape, theme, &mut rng);
                 map.apply_room_to_map(&new_room);
-    
-                if !map.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len()-1].center();
-    
-                    if rng.range(0,2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
+                map.rooms.push(new_room);
+            }
+        }
+
+        let centers: Vec<Point> = map.rooms.iter().map(|room| {
+            let (x, y) = room.center();
+            Point::new(x, y)
+        }).collect();
+        for (a, b) in plan_connections(&centers, corridors.strategy, &mut rng) {
+            map.apply_corridor(map.rooms[a].center(), map.rooms[b].center(), &corridors, &mut rng);
+        }
+
+        const MAX_VAULTS: i32 = 2;
+        const VAULT_ATTEMPTS: i32 = 20;
+
+        let mut vaults = 0;
+        for _ in 0..VAULT_ATTEMPTS {
+            if vaults >= MAX_VAULTS { break; }
+            if let Some(vault) = map.try_add_vault(&mut rng) {
+                map.rooms.push(vault);
+                vaults += 1;
             }
-    
-            map.rooms.push(new_room);
         }
 
+        map.populate_blocked();
         map
     }
 
@@ -188,16 +492,91 @@ impl Map {
         map
     }
 
+    /// The room whose floor covers (x, y), if any.
+    pub fn room_at(&self, x: i32, y: i32) -> Option<&Room> {
+        self.rooms.iter().find(|room| room.contains(x, y))
+    }
+
+    /// One message per kind of notable room on the level, for the player to read on arrival.
+    pub fn level_feelings(&self) -> Vec<String> {
+        let mut feelings: Vec<String> = Vec::new();
+        for room in self.rooms.iter() {
+            if let Some(feeling) = room.theme.feeling() {
+                if !feelings.iter().any(|f| f == feeling) {
+                    feelings.push(feeling.to_string());
+                }
+            }
+        }
+        feelings
+    }
+
     fn is_exit_valid(&self, x:i32, y:i32) -> bool {
         if x < 1 || x > self.width-1 || y < 1 || y > self.height-1 { return false; }
         let idx = self.xy_idx(x, y);
         !self.blocked[idx]
     }
 
+    /// The tiles next to `center` that could be walked into and have nothing on them.
+    pub fn free_tiles_around(&self, center: Point) -> Vec<Point> {
+        let mut result = Vec::new();
+        for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+            let (x, y) = (center.x + dx, center.y + dy);
+            if self.is_exit_valid(x, y) && self.tile_content[self.xy_idx(x, y)].is_empty() {
+                result.push(Point::new(x, y));
+            }
+        }
+        result
+    }
+
+    /// Recomputes what blocks movement and sight from the tiles. Call after changing tiles
+    /// directly.
     pub fn populate_blocked(&mut self) {
-        for (i, tile) in self.tiles.iter_mut().enumerate() {
-            self.blocked[i] = *tile == TileType::Wall;
+        for idx in 0..self.tiles.len() {
+            self.refresh_blocked(idx);
+            self.refresh_opacity(idx);
+        }
+    }
+
+    fn refresh_opacity(&mut self, idx: usize) {
+        let opaque = self.tiles[idx] != TileType::Floor;
+        if self.opaque[idx] != opaque {
+            self.opaque[idx] = opaque;
+            self.fov_cache.clear();
+        }
+    }
+
+    /// The tiles a projectile passes through on its way from `from` to `to`, not counting `from`.
+    /// Stops early at the first tile that blocks sight, which is included so callers can tell
+    /// what it hit.
+    pub fn projectile_path(&self, from: Point, to: Point) -> Vec<Point> {
+        let mut path: Vec<Point> = Vec::new();
+        for point in line2d_bresenham(from, to).into_iter().skip(1) {
+            if point.x < 0 || point.x >= self.width || point.y < 0 || point.y >= self.height { break; }
+            path.push(point);
+            if self.opaque[self.xy_idx(point.x, point.y)] { break; }
+        }
+        path
+    }
+
+    /// True if nothing solid stands between `from` and `to`. Creatures in the way don't count.
+    pub fn has_line_of_fire(&self, from: Point, to: Point) -> bool {
+        from == to || self.projectile_path(from, to).last() == Some(&to)
+    }
+
+    /// Everything visible from `origin` within `range`. Results are remembered per tile, so
+    /// viewers standing still or returning to a spot they've seen from before cost nothing.
+    pub fn field_of_view(&mut self, origin: Point, range: i32) -> Vec<Point> {
+        let key = (self.xy_idx(origin.x, origin.y), range);
+        if let Some(visible) = self.fov_cache.get(&key) {
+            return visible.clone();
+        }
+
+        let visible = symmetric_fov(origin, range, self.width, self.height, &self.opaque);
+        if self.fov_cache.len() >= FOV_CACHE_LIMIT {
+            self.fov_cache.clear();
         }
+        self.fov_cache.insert(key, visible.clone());
+        visible
     }
 }
 
@@ -216,13 +595,21 @@ pub fn draw_map(ecs: &World, ctx: &mut BTerm) {
                     glyph = bracket_lib::prelude::to_cp437('.');
                     foreground = RGB::from_f32(0.0, 0.5, 0.5);
                 }
-                TileType::Wall => {
+                TileType::Wall | TileType::SecretDoor => {
                     glyph = bracket_lib::prelude::to_cp437('#');
                     foreground = RGB::from_f32(0., 1.0, 0.);
                 }
+                TileType::Door => {
+                    glyph = bracket_lib::prelude::to_cp437('+');
+                    foreground = RGB::from_f32(0.8, 0.5, 0.1);
+                }
+            }
+            let mut background = if map.is_bloodstained(idx) { RGB::from_f32(0.5, 0., 0.) } else { RGB::from_f32(0., 0., 0.) };
+            if !map.visible_tiles[idx] {
+                foreground = foreground.to_greyscale();
+                background = background.to_greyscale();
             }
-            if !map.visible_tiles[idx] { foreground = foreground.to_greyscale() }
-            ctx.set(x,y,foreground,RGB::from_f32(0., 0., 0.), glyph);
+            ctx.set(x,y,foreground,background, glyph);
         }
         
         // Move coordinates
@@ -233,3 +620,153 @@ pub fn draw_map(ecs: &World, ctx: &mut BTerm) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10x10 map of open floor, and a world to make entities in.
+    fn open_map() -> (Map, World) {
+        let mut map = Map::new(10, 10);
+        for tile in map.tiles.iter_mut() {
+            *tile = TileType::Floor;
+        }
+        map.populate_blocked();
+        (map, World::new())
+    }
+
+    #[test]
+    fn index_entity_adds_to_tile_and_blocks() {
+        let (mut map, mut world) = open_map();
+        let entity = world.create_entity().build();
+        map.index_entity(entity, 12, true);
+
+        assert_eq!(map.entities_at(12), &[entity]);
+        assert!(map.blocked[12]);
+    }
+
+    #[test]
+    fn non_blocking_entity_leaves_tile_open() {
+        let (mut map, mut world) = open_map();
+        let entity = world.create_entity().build();
+        map.index_entity(entity, 12, false);
+
+        assert_eq!(map.entities_at(12), &[entity]);
+        assert!(!map.blocked[12]);
+    }
+
+    #[test]
+    fn reindexing_moves_instead_of_duplicating() {
+        let (mut map, mut world) = open_map();
+        let entity = world.create_entity().build();
+        map.index_entity(entity, 12, true);
+        map.index_entity(entity, 12, true);
+        map.index_entity(entity, 30, true);
+
+        assert!(map.entities_at(12).is_empty());
+        assert!(!map.blocked[12]);
+        assert_eq!(map.entities_at(30), &[entity]);
+        assert!(map.blocked[30]);
+    }
+
+    #[test]
+    fn move_entity_keeps_blocking_flag() {
+        let (mut map, mut world) = open_map();
+        let blocker = world.create_entity().build();
+        let item = world.create_entity().build();
+        map.index_entity(blocker, 12, true);
+        map.index_entity(item, 13, false);
+        map.move_entity(blocker, 22);
+        map.move_entity(item, 23);
+
+        assert!(!map.blocked[12] && map.blocked[22]);
+        assert!(!map.blocked[23]);
+        assert_eq!(map.entities_at(22), &[blocker]);
+        assert_eq!(map.entities_at(23), &[item]);
+    }
+
+    #[test]
+    fn move_entity_ignores_unindexed_entities() {
+        let (mut map, mut world) = open_map();
+        let entity = world.create_entity().build();
+        map.move_entity(entity, 12);
+
+        assert!(map.entities_at(12).is_empty());
+        assert!(!map.blocked[12]);
+    }
+
+    #[test]
+    fn unindexing_one_blocker_keeps_tile_blocked_by_another() {
+        let (mut map, mut world) = open_map();
+        let first = world.create_entity().build();
+        let second = world.create_entity().build();
+        map.index_entity(first, 12, true);
+        map.index_entity(second, 12, true);
+
+        map.unindex_entity(first.id());
+        assert_eq!(map.entities_at(12), &[second]);
+        assert!(map.blocked[12]);
+
+        map.unindex_entity(second.id());
+        assert!(map.entities_at(12).is_empty());
+        assert!(!map.blocked[12]);
+    }
+
+    #[test]
+    fn unindexing_leaves_walls_blocked() {
+        let (mut map, mut world) = open_map();
+        map.tiles[12] = TileType::Wall;
+        map.populate_blocked();
+        let entity = world.create_entity().build();
+        map.index_entity(entity, 12, true);
+        map.unindex_entity(entity.id());
+
+        assert!(map.blocked[12]);
+    }
+
+    #[test]
+    fn entities_in_radius_only_finds_those_in_range() {
+        let (mut map, mut world) = open_map();
+        let near = world.create_entity().build();
+        let edge = world.create_entity().build();
+        let far = world.create_entity().build();
+        map.index_entity(near, map.xy_idx(5, 5), false);
+        map.index_entity(edge, map.xy_idx(7, 5), false);
+        map.index_entity(far, map.xy_idx(7, 7), false);
+
+        let found = map.entities_in_radius(Point::new(5, 5), 2);
+        assert!(found.contains(&near));
+        assert!(found.contains(&edge));
+        assert!(!found.contains(&far));
+    }
+
+    #[test]
+    fn reveal_row_skips_solid_rock() {
+        let mut map = Map::new(10, 5);
+        for x in 2..5 {
+            let idx = map.xy_idx(x, 2);
+            map.tiles[idx] = TileType::Floor;
+        }
+        let door = map.xy_idx(5, 2);
+        map.tiles[door] = TileType::Door;
+        for y in 0..5 {
+            map.reveal_row(y);
+        }
+
+        let revealed = |x, y| map.revealed_tiles[map.xy_idx(x, y)];
+        assert!(revealed(3, 2) && revealed(5, 2));
+        // Walls around the corridor, corners included
+        assert!(revealed(1, 1) && revealed(3, 1) && revealed(5, 3) && revealed(1, 2));
+        // Rock nowhere near a floor, including beside the door
+        assert!(!revealed(0, 2) && !revealed(3, 0) && !revealed(6, 2) && !revealed(8, 4));
+    }
+
+    #[test]
+    fn entities_in_radius_clips_to_the_map() {
+        let (mut map, mut world) = open_map();
+        let corner = world.create_entity().build();
+        map.index_entity(corner, 0, false);
+
+        assert_eq!(map.entities_in_radius(Point::new(0, 0), 3), vec![corner]);
+    }
+}
@@ -1,32 +1,42 @@
 use specs::prelude::*;
+use specs::storage::ComponentEvent;
 use super::{Map, Position, BlocksTile};
 
+/// Our place in the `Position` event channel, kept between runs since the system itself
+/// is rebuilt every tick.
+pub struct PositionReader {
+    pub reader: ReaderId<ComponentEvent>
+}
+
+/// Keeps the map's spatial index in step with entity spawns, moves and despawns. Only entities
+/// whose `Position` changed since the last run are touched.
 pub struct MapIndexingSystem {}
 
 impl<'a> System<'a> for MapIndexingSystem {
     type SystemData = (
         WriteExpect<'a, Map>,
+        WriteExpect<'a, PositionReader>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, BlocksTile>,
         Entities<'a>,
     );
 
     fn run (&mut self, data: Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, mut position_reader, positions, blockers, entities) = data;
 
-        map.populate_blocked();
-        map.clear_content_index();
-        for (entity, position) in (&entities, &position).join() {
-            let idx = map.xy_idx(position.x, position.y);
-            // If the entity blocks, update the blocking list
-            let _p : Option<&BlocksTile> = blockers.get(entity);
-            if let Some(_p) = _p {
-                map.blocked[idx] = true;
+        for event in positions.channel().read(&mut position_reader.reader) {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    // The entity may have lost its position (or died) since the event was sent;
+                    // a later Removed event will take care of it.
+                    let entity = entities.entity(*id);
+                    if let Some(position) = positions.get(entity) {
+                        let idx = map.xy_idx(position.x, position.y);
+                        map.index_entity(entity, idx, blockers.contains(entity));
+                    }
+                }
+                ComponentEvent::Removed(id) => map.unindex_entity(*id),
             }
-            
-            // Push the entity to the appropriate index slot. It's a Copy type, 
-            // so we don't need to clone it (we want to avoid moving it out of the ECS!)
-            map.tile_content[idx].push(entity);
         }
     }
 }
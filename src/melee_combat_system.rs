@@ -1,5 +1,14 @@
 use specs::prelude::*;
-use super::{CombatStats, WantsToMelee, Name, SufferDamage, gamelog::GameLog};
+use bracket_lib::prelude::RandomNumberGenerator;
+use super::{AttackRoll, roll_attack, roll_damage, CombatStats, MeleeDamage, DamageType, WantsToMelee, Name, SufferDamage, gamelog::GameLog, Position, Awareness, AwarenessState,
+    NoiseEvents, KnocksBack, Knockback, Map, Equipped, MeleePowerBonus, DefenseBonus, power_bonus, defense_bonus, wielded_damage};
+use bracket_lib::prelude::Point;
+
+/// How far the sound of a fight carries.
+const COMBAT_VOLUME: i32 = 8;
+
+/// Bare hands, for anything that fights without a `MeleeDamage` of its own.
+const UNARMED: MeleeDamage = MeleeDamage{ n_dice: 1, die_type: 2, bonus: 0, kind: DamageType::Slashing };
 
 pub struct MeleeCombatSystem {}
 
@@ -11,23 +20,72 @@ impl<'a> System<'a> for MeleeCombatSystem {
                         ReadStorage<'a, Name>,
                         ReadStorage<'a, CombatStats>,
                         WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, NoiseEvents>,
+                        ReadStorage<'a, Position>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, MeleeDamage>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        ReadStorage<'a, KnocksBack>,
+                        WriteStorage<'a, Knockback>,
+                        ReadExpect<'a, Map>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenseBonus>
                     );
     fn run(&mut self, data: Self::SystemData) {
-        let (entities,mut log, mut wants_melee, names, combat_stats, mut inflict_damage) = data;
+        let (entities,mut log, mut wants_melee, names, combat_stats, mut inflict_damage, mut noise, positions, mut awareness,
+            melee_damage, mut rng, knocks_back, mut knockback, map, equipped, power_bonuses, defense_bonuses) = data;
         
-        for (_entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
+        for (entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
             if stats.hp > 0 {
                 let target_stats = combat_stats.get(wants_melee.target).unwrap();
                 if target_stats.hp > 0 {
+                    // Whatever happens, the target knows it's in a fight and the neighbours hear it
+                    if let (Some(aware), Some(pos)) = (awareness.get_mut(wants_melee.target), positions.get(entity)) {
+                        aware.state = AwarenessState::Hunting{ target: entity, last_seen: Point::new(pos.x, pos.y) };
+                    }
+                    if let Some(pos) = positions.get(entity) {
+                        noise.make_noise(pos.x, pos.y, COMBAT_VOLUME);
+                    }
+
                     let target_name = names.get(wants_melee.target).unwrap();
+                    // Fights out of sight are only heard about
+                    let seen = positions.get(entity).is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)]);
+
+                    let power = stats.power + power_bonus(entity, &equipped, &power_bonuses);
+                    let defense = target_stats.defense + defense_bonus(wants_melee.target, &equipped, &defense_bonuses);
+                    match roll_attack(&mut rng, power, defense) {
+                        AttackRoll::Fumble => {
+                            if seen {
+                                log.entries.push(format!("{} fumbles an attack on {}.", &name.name, &target_name.name));
+                            }
+                        }
+                        AttackRoll::Miss => {
+                            if seen {
+                                log.entries.push(format!("{} misses {}.", &name.name, &target_name.name));
+                            }
+                        }
+                        roll => {
+                            let critical = roll == AttackRoll::Critical;
+                            let weapon = wielded_damage(entity, &equipped, &melee_damage)
+                                .or_else(|| melee_damage.get(entity).copied())
+                                .unwrap_or(UNARMED);
+                            let damage = roll_damage(&mut rng, weapon.n_dice, weapon.die_type, weapon.bonus, critical);
 
-                    let damage = i32::max(0, stats.power - target_stats.defense);
+                            if seen {
+                                if critical {
+                                    log.entries.push(format!("{} critically hits {}, for {} {} damage!", &name.name, &target_name.name, damage, weapon.kind.name()));
+                                } else {
+                                    log.entries.push(format!("{} hits {}, for {} {} damage.", &name.name, &target_name.name, damage, weapon.kind.name()));
+                                }
+                            }
+                            SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage, weapon.kind);
 
-                    if damage == 0 {
-                        log.entries.push(format!("{} is unable to hurt {}", &name.name, &target_name.name));
-                    } else {
-                        log.entries.push(format!("{} hits {}, for {} hp.", &name.name, &target_name.name, damage));
-                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                            if let (Some(knocks), Some(pos)) = (knocks_back.get(entity), positions.get(entity)) {
+                                knockback.insert(wants_melee.target, Knockback{ from: Point::new(pos.x, pos.y), distance: knocks.distance })
+                                    .expect("Unable to insert knockback");
+                            }
+                        }
                     }
                 }
             }
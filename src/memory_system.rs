@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use bracket_lib::prelude::{FontCharType, RGB};
+use super::{Map, Position, Renderable, Name, Player, GameTurn, Unidentified, display_name};
+
+/// What the player last saw of an entity, and where.
+pub struct RememberedEntity {
+    pub x: i32,
+    pub y: i32,
+    pub glyph: FontCharType,
+    pub foreground: RGB,
+    pub render_order: i32,
+    pub name: Option<String>,
+    pub last_seen: i32
+}
+
+/// The player's picture of the map's contents outside their field of view.
+#[derive(Default)]
+pub struct EntityMemory {
+    pub entities: HashMap<Entity, RememberedEntity>
+}
+
+impl EntityMemory {
+    pub fn at(&self, x: i32, y: i32) -> impl Iterator<Item = &RememberedEntity> {
+        self.entities.values().filter(move |memory| memory.x == x && memory.y == y)
+    }
+}
+
+/// Records everything the player can currently see, and forgets anything remembered on a
+/// visible tile that turns out not to be there any more.
+pub struct MemorySystem {}
+
+impl<'a> System<'a> for MemorySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Map>,
+                        ReadExpect<'a, GameTurn>,
+                        WriteExpect<'a, EntityMemory>,
+                        Entities<'a>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Renderable>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Unidentified>,
+                        ReadStorage<'a, Player>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, turn, mut memory, entities, positions, renderables, names, unidentified, players) = data;
+
+        // Anything on a tile the player has forgotten goes with it. Otherwise an entry stays put
+        // until the player can see it's wrong: a creature that has slipped out of view is still
+        // remembered where it was last seen, while one seen somewhere else is recorded afresh below.
+        memory.entities.retain(|entity, remembered| {
+            let idx = map.xy_idx(remembered.x, remembered.y);
+            if !map.revealed_tiles[idx] { return false; }
+            if !map.visible_tiles[idx] { return true; }
+            match positions.get(*entity) {
+                Some(pos) => (pos.x == remembered.x && pos.y == remembered.y) || !map.visible_tiles[map.xy_idx(pos.x, pos.y)],
+                None => false
+            }
+        });
+
+        for (entity, pos, render, _player) in (&entities, &positions, &renderables, !&players).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            if !map.visible_tiles[idx] { continue; }
+
+            memory.entities.insert(entity, RememberedEntity {
+                x: pos.x,
+                y: pos.y,
+                glyph: render.glyph,
+                foreground: render.foreground,
+                render_order: render.render_order,
+                name: names.get(entity).map(|_| display_name(entity, &names, &unidentified)),
+                last_seen: turn.number
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bracket_lib::prelude::{Point, RandomNumberGenerator};
+    use crate::{register_components, spawner, GameLog, RunState, TileType, VisibilitySystem};
+
+    /// A lit room from x 1 to 9 with the player at (2, 5), and an unlit one beyond a wall at x 10.
+    fn world() -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        let mut map = Map::new(20, 10);
+        for y in 1..9 {
+            for x in 1..19 {
+                if x != 10 {
+                    let idx = map.xy_idx(x, y);
+                    map.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+        map.populate_blocked();
+        ecs.insert(map);
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        ecs.insert(GameLog{ entries: Vec::new() });
+        ecs.insert(GameTurn{ number: 0 });
+        ecs.insert(RunState::PlayerTurn);
+        ecs.insert(EntityMemory::default());
+        ecs.insert(Point::new(2, 5));
+        let player = spawner::player(&mut ecs, 2, 5);
+        ecs.insert(player);
+        ecs
+    }
+
+    fn orc(ecs: &mut World, x: i32, y: i32) -> Entity {
+        spawner::orc(ecs, x, y);
+        let monsters = ecs.read_storage::<crate::Monster>();
+        (&ecs.entities(), &monsters).join().map(|(entity, _monster)| entity).next().unwrap()
+    }
+
+    fn look(ecs: &mut World) {
+        VisibilitySystem{}.run_now(ecs);
+        MemorySystem{}.run_now(ecs);
+    }
+
+    fn move_to(ecs: &mut World, entity: Entity, x: i32, y: i32) {
+        *ecs.write_storage::<Position>().get_mut(entity).unwrap() = Position{ x, y };
+    }
+
+    fn remembered_at(ecs: &World, entity: Entity) -> Option<(i32, i32)> {
+        ecs.fetch::<EntityMemory>().entities.get(&entity).map(|remembered| (remembered.x, remembered.y))
+    }
+
+    #[test]
+    fn walking_out_of_view_is_remembered_at_last_tile() {
+        let mut ecs = world();
+        let orc = orc(&mut ecs, 8, 5);
+        look(&mut ecs);
+        assert_eq!(remembered_at(&ecs, orc), Some((8, 5)));
+
+        // Moves behind the wall after the player's view was worked out, as in a monster turn
+        VisibilitySystem{}.run_now(&ecs);
+        move_to(&mut ecs, orc, 12, 5);
+        MemorySystem{}.run_now(&ecs);
+        assert_eq!(remembered_at(&ecs, orc), Some((8, 5)));
+
+        look(&mut ecs);
+        assert_eq!(remembered_at(&ecs, orc), Some((8, 5)));
+    }
+
+    #[test]
+    fn seen_somewhere_else_moves_the_memory() {
+        let mut ecs = world();
+        let orc = orc(&mut ecs, 8, 5);
+        look(&mut ecs);
+        move_to(&mut ecs, orc, 6, 3);
+        look(&mut ecs);
+        assert_eq!(remembered_at(&ecs, orc), Some((6, 3)));
+    }
+
+    #[test]
+    fn gone_from_a_visible_tile_is_forgotten() {
+        let mut ecs = world();
+        let orc = orc(&mut ecs, 8, 5);
+        look(&mut ecs);
+        ecs.delete_entity(orc).unwrap();
+        ecs.maintain();
+        look(&mut ecs);
+        assert_eq!(remembered_at(&ecs, orc), None);
+    }
+}
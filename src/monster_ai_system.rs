@@ -1,52 +1,402 @@
 use specs::prelude::*;
-use super::{Viewshed, Monster, Map, Position, WantsToMelee, RunState};
-use bracket_lib::prelude::Point;
+use super::{Viewshed, Monster, Map, Position, WantsToMelee, WantsToShoot, RunState, Awareness, AwarenessState, Perception,
+    Stealth, Name, gamelog::GameLog, GameTurn, StatusEffects, StatusKind,
+    Corpse, Abilities, AbilityKind, WantsToUseAbility, in_fov_range, InBackpack, RangedWeapon, Ammunition, ranged_weapon_of, ammunition_of,
+    CombatStats, Faction, Reaction, Reactions, Equipped};
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
 
+/// Base difficulty for a monster to spot something, before the other's stealth is added.
+const NOTICE_DC: i32 = 10;
+
+/// Sends monsters after whatever they're hunting: the player, or any creature their faction is
+/// hostile to.
 pub struct MonsterAI {}
 
+fn distance(a: Point, b: Point) -> f32 {
+    bracket_lib::geometry::DistanceAlg::Pythagoras.distance2d(a, b)
+}
+
+/// Takes one step along the shortest path to `target`. Returns false if there is no way there.
+fn step_towards(map: &mut Map, entity: Entity, pos: &mut Position, viewshed: &mut Viewshed, target: Point) -> bool {
+    // Whatever's standing on the target blocks it, but that shouldn't stop a path to it
+    let target_idx = map.xy_idx(target.x, target.y);
+    let target_blocked = map.blocked[target_idx];
+    map.blocked[target_idx] = false;
+    let path = bracket_lib::pathfinding::a_star_search(
+        map.xy_idx(pos.x, pos.y) as i32,
+        target_idx as i32,
+        &*map
+    );
+    map.blocked[target_idx] = target_blocked;
+
+    if path.success && path.steps.len() > 1 {
+        pos.x = path.steps[1] as i32 % map.width;
+        pos.y = path.steps[1] as i32 / map.width;
+
+        // Update the index straight away so monsters moving later this turn
+        // path around us.
+        let idx = map.xy_idx(pos.x, pos.y);
+        map.move_entity(entity, idx);
+        viewshed.dirty = true;
+        return true;
+    }
+    false
+}
+
 impl<'a> System<'a> for MonsterAI {
     #[allow(clippy::type_complexity)]
     type SystemData = ( WriteExpect<'a, Map>,
-                        ReadExpect<'a, Point>,
                         ReadExpect<'a, Entity>,
                         ReadExpect<'a, RunState>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, GameLog>,
                         Entities<'a>,
                         WriteStorage<'a, Viewshed>,
                         ReadStorage<'a, Monster>,
                         WriteStorage<'a, Position>,
-                        WriteStorage<'a, WantsToMelee>);
-    
+                        WriteStorage<'a, WantsToMelee>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, Perception>,
+                        ReadStorage<'a, Stealth>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, WantsToShoot>,
+                        (ReadStorage<'a, InBackpack>, ReadStorage<'a, RangedWeapon>, ReadStorage<'a, Ammunition>),
+                        ReadExpect<'a, GameTurn>,
+                        ReadStorage<'a, StatusEffects>,
+                        ReadStorage<'a, Corpse>,
+                        WriteStorage<'a, Abilities>,
+                        WriteStorage<'a, WantsToUseAbility>,
+                        ReadExpect<'a, Reactions>,
+                        ReadStorage<'a, Faction>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Equipped>);
+
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, player_pos, player_entity, runstate, entities, mut viewshed, monster, mut position, mut wants_to_melee) = data;
+        let (mut map, player_entity, runstate, mut rng, mut log, entities, mut viewshed, monster,
+            mut position, mut wants_to_melee, mut awareness, perception, stealth, names, mut wants_to_shoot,
+            (backpack, weapons, ammunition), turn, statuses, corpses, mut abilities, mut wants_ability,
+            reactions, factions, combat_stats, equipped) = data;
 
         if *runstate != RunState::MonsterTurn { return; }
-        for (entity, viewshed, _monster, pos) in (&entities, &mut viewshed, &monster, &mut position).join() {
-            let monster_pos = Point::new(pos.x, pos.y);
-            let current_distance = bracket_lib::geometry::DistanceAlg::Pythagoras.distance2d(monster_pos, *player_pos);
-            if current_distance < 1.5 {
-                // Attack here
-                wants_to_melee.insert(entity, WantsToMelee { target: *player_entity }).expect("Unable to insert attack");
-            } else if viewshed.visible_tiles.contains(&*player_pos) {
-                // Path to the player
-                let path = bracket_lib::pathfinding::a_star_search(
-                    map.xy_idx(pos.x, pos.y) as i32, // Monster position
-                    map.xy_idx(player_pos.x, player_pos.y) as i32, // Player position
-                    &mut *map
-                );
-
-                if path.success && path.steps.len() > 1 {
-                    let mut idx = map.xy_idx(pos.x, pos.y);
-                    map.blocked[idx] = false;
-
-                    pos.x = path.steps[1] as i32 % map.width;
-                    pos.y = path.steps[1] as i32 / map.width;
-                    
-                    idx = map.xy_idx(pos.x, pos.y);
-                    map.blocked[idx] = true;
-                    viewshed.dirty = true;
+
+        let has_status = |entity: Entity, kind: StatusKind| statuses.get(entity).is_some_and(|s| s.has(kind));
+
+        // Everything else is slow to a hasted player
+        if has_status(*player_entity, StatusKind::Hasted) && turn.number % 2 == 1 { return; }
+
+        // A blind player's view says nothing about what can see them
+        let player_range = if has_status(*player_entity, StatusKind::Blinded) { 0 } else { viewshed.get(*player_entity).map_or(0, |v| v.range) };
+
+        // How far a monster can shoot, if it has a ranged weapon and something to fire from it
+        let shooting_range = |entity: Entity| {
+            ranged_weapon_of(entity, &entities, &backpack, &weapons)
+                .filter(|_weapon| ammunition_of(entity, &entities, &backpack, &ammunition).is_some())
+                .map(|weapon| weapon.range as f32)
+        };
+        let alive = |entity: Entity| entities.is_alive(entity) && combat_stats.get(entity).is_some_and(|stats| stats.hp > 0);
+
+        // Everything that could be picked as a target
+        let creatures: Vec<(Entity, Faction)> = (&entities, &factions, &combat_stats).join()
+            .filter(|(_entity, _faction, stats)| stats.hp > 0)
+            .map(|(entity, faction, _stats)| (entity, *faction))
+            .collect();
+        let monsters: Vec<Entity> = (&entities, &monster, &awareness).join().map(|(entity, _monster, _awareness)| entity).collect();
+
+        for entity in monsters {
+            let mut state = awareness.get(entity).unwrap().state;
+            // Sleeping monsters only wake up to noise or being hit
+            if state == AwarenessState::Asleep || has_status(entity, StatusKind::Asleep) { continue; }
+            // Confused monsters spend their turn working out where they are
+            if has_status(entity, StatusKind::Confused) { continue; }
+            if has_status(entity, StatusKind::Slowed) && turn.number % 2 == 1 { continue; }
+            let steps = if has_status(entity, StatusKind::Hasted) { 2 } else { 1 };
+            let blind = has_status(entity, StatusKind::Blinded);
+
+            let monster_pos = match position.get(entity) {
+                Some(pos) => Point::new(pos.x, pos.y),
+                None => continue
+            };
+            let view = match viewshed.get(entity) {
+                Some(view) => view,
+                None => continue
+            };
+
+            // Sight is symmetric, so if the monster is inside the player's field of view it can
+            // see the player too. That's one lookup instead of a search through every viewshed.
+            let can_see = |other: Entity| -> Option<Point> {
+                if blind { return None; }
+                let at = position.get(other).map(|pos| Point::new(pos.x, pos.y))?;
+                let seen = if other == *player_entity && view.range <= player_range {
+                    map.visible_tiles[map.xy_idx(monster_pos.x, monster_pos.y)] && in_fov_range(monster_pos, at, view.range)
+                } else {
+                    view.visible_tiles.contains(&at)
+                };
+                if seen { Some(at) } else { None }
+            };
+
+            // Hostile creatures in view, nearest first
+            let own_faction = factions.get(entity).copied();
+            let mut hostiles: Vec<(Entity, Point)> = creatures.iter()
+                .filter(|(other, faction)| *other != entity
+                    && own_faction.is_some_and(|own| reactions.reaction(own, *faction) == Reaction::Hostile))
+                .filter_map(|(other, _faction)| can_see(*other).map(|at| (*other, at)))
+                .collect();
+            hostiles.sort_by(|a, b| distance(monster_pos, a.1).total_cmp(&distance(monster_pos, b.1)));
+
+            // Killed whatever it was after
+            if let AwarenessState::Hunting{ target, .. } = state {
+                if !alive(target) { state = AwarenessState::Unaware; }
+            }
+
+            // Something that isn't already being hunted has to be noticed first
+            if !matches!(state, AwarenessState::Hunting{..}) {
+                if let Some((target, at)) = hostiles.first() {
+                    let bonus = perception.get(entity).map_or(0, |p| p.value);
+                    let dc = NOTICE_DC + stealth.get(*target).map_or(0, |s| s.value);
+                    if rng.roll_dice(1, 20) + bonus >= dc {
+                        state = AwarenessState::Hunting{ target: *target, last_seen: *at };
+                        if *target == *player_entity {
+                            if let Some(name) = names.get(entity) {
+                                log.entries.push(format!("{} notices you!", name.name));
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Keep track of where the target is while it's in view. Out of sight, go for anything
+            // else hostile that's in view instead.
+            let mut target_pos = None;
+            if let AwarenessState::Hunting{ target, .. } = state {
+                target_pos = can_see(target);
+                if let Some(at) = target_pos {
+                    state = AwarenessState::Hunting{ target, last_seen: at };
+                } else if let Some((other, at)) = hostiles.first() {
+                    state = AwarenessState::Hunting{ target: *other, last_seen: *at };
+                    target_pos = Some(*at);
+                }
+            }
+
+            // An ability that's ready and worth using comes before anything else
+            if let Some(abilities) = abilities.get_mut(entity) {
+                let hunted = match state {
+                    AwarenessState::Hunting{ target, .. } => target_pos.map(|at| (target, at)),
+                    _ => None
+                };
+                let in_reach = |at: Point, range: f32| distance(monster_pos, at) <= range && map.has_line_of_fire(monster_pos, at);
+
+                let mut chosen = None;
+                for ability in abilities.abilities.iter_mut().filter(|ability| turn.number >= ability.ready_at) {
+                    // None if it isn't worth using now, otherwise what to use it on
+                    let target: Option<Option<Entity>> = match ability.kind {
+                        AbilityKind::Breath{ radius, .. } => hunted
+                            .filter(|(_target, at)| in_reach(*at, radius as f32))
+                            .map(|(target, _at)| Some(target)),
+                        AbilityKind::Spell{ range, .. } => hunted
+                            .filter(|(_target, at)| in_reach(*at, range))
+                            .map(|(target, _at)| Some(target)),
+                        AbilityKind::Summon{ .. } => hunted.map(|_hunted| None),
+                        // Only worth it if there's something not worn to take
+                        AbilityKind::Steal => hunted
+                            .filter(|(target, at)| distance(monster_pos, *at) < 1.5
+                                && (&backpack, !&equipped).join().any(|(pack, _worn)| pack.owner == *target))
+                            .map(|(target, _at)| Some(target)),
+                        AbilityKind::HealAllies{ range, .. } => {
+                            // Whoever's worst off, as long as they're down to half
+                            creatures.iter()
+                                .filter(|(other, faction)| *other == entity
+                                    || (own_faction.is_some_and(|own| reactions.reaction(own, *faction) == Reaction::Friendly)
+                                        && can_see(*other).is_some_and(|at| distance(monster_pos, at) <= range)))
+                                .filter_map(|(other, _faction)| combat_stats.get(*other).map(|stats| (*other, stats)))
+                                .filter(|(_other, stats)| stats.hp * 2 <= stats.max_hp)
+                                .min_by(|(_a, a), (_b, b)| (a.hp as f32 / a.max_hp as f32).total_cmp(&(b.hp as f32 / b.max_hp as f32)))
+                                .map(|(other, _stats)| Some(other))
+                        }
+                        AbilityKind::RaiseDead{ range } => {
+                            view.visible_tiles.iter()
+                                .filter(|tile| !blind && distance(monster_pos, **tile) <= range)
+                                .flat_map(|tile| map.entities_at(map.xy_idx(tile.x, tile.y)).iter())
+                                .find(|other| corpses.contains(**other))
+                                .map(|corpse| Some(*corpse))
+                        }
+                        // Happens by itself when hurt
+                        AbilityKind::Split => None
+                    };
+                    if let Some(target) = target {
+                        ability.ready_at = turn.number + ability.cooldown;
+                        chosen = Some(WantsToUseAbility{ kind: ability.kind, target });
+                        break;
+                    }
+                }
+
+                if let Some(chosen) = chosen {
+                    wants_ability.insert(entity, chosen).expect("Unable to insert ability");
+                    awareness.get_mut(entity).unwrap().state = state;
+                    continue;
+                }
+            }
+
+            match state {
+                AwarenessState::Hunting{ target, last_seen } => {
+                    match target_pos {
+                        Some(target_pos) => {
+                            let current_distance = distance(monster_pos, target_pos);
+                            if current_distance < 1.5 {
+                                // Attack here
+                                wants_to_melee.insert(entity, WantsToMelee { target }).expect("Unable to insert attack");
+                            } else if shooting_range(entity).is_some_and(|range| current_distance <= range)
+                                && map.has_line_of_fire(monster_pos, target_pos) {
+                                // Archers would rather shoot than close in
+                                wants_to_shoot.insert(entity, WantsToShoot { target }).expect("Unable to insert shot");
+                            } else {
+                                let pos = position.get_mut(entity).unwrap();
+                                let view = viewshed.get_mut(entity).unwrap();
+                                for _ in 0..steps {
+                                    // Stop once alongside; the player's tile isn't blocked
+                                    if distance(Point::new(pos.x, pos.y), target_pos) < 1.5 { break; }
+                                    step_towards(&mut map, entity, pos, view, target_pos);
+                                }
+                            }
+                        }
+                        None => {
+                            // Lost sight of them; go and look where they were last seen
+                            state = AwarenessState::Investigating{ x: last_seen.x, y: last_seen.y };
+                        }
+                    }
+                }
+                AwarenessState::Investigating{ x, y } => {
+                    let pos = position.get_mut(entity).unwrap();
+                    let view = viewshed.get_mut(entity).unwrap();
+                    for _ in 0..steps {
+                        let arrived = pos.x == x && pos.y == y;
+                        if arrived || !step_towards(&mut map, entity, pos, view, Point::new(x, y)) {
+                            state = AwarenessState::Unaware;
+                            break;
+                        }
+                    }
                 }
+                AwarenessState::Unaware | AwarenessState::Asleep => {}
+            }
+
+            awareness.get_mut(entity).unwrap().state = state;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{register_components, spawner, Reactions, VisibilitySystem};
+
+    /// An open 40x40 floor with the player in a far corner, out of everyone's sight.
+    fn world() -> (World, Entity) {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        let mut map = Map::new(40, 40);
+        for y in 1..39 {
+            for x in 1..39 {
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = crate::TileType::Floor;
             }
-        
         }
+        map.populate_blocked();
+        ecs.insert(map);
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        ecs.insert(GameLog{ entries: Vec::new() });
+        ecs.insert(GameTurn{ number: 0 });
+        ecs.insert(Reactions::default());
+        ecs.insert(RunState::MonsterTurn);
+        ecs.insert(Point::new(35, 35));
+        let player = spawner::player(&mut ecs, 35, 35);
+        ecs.insert(player);
+        (ecs, player)
+    }
+
+    fn spawn(ecs: &mut World, spawn: fn(&mut World, i32, i32), x: i32, y: i32) -> Entity {
+        spawn(ecs, x, y);
+        let positions = ecs.read_storage::<Position>();
+        (&ecs.entities(), &positions).join()
+            .find(|(_entity, pos)| pos.x == x && pos.y == y)
+            .map(|(entity, _pos)| entity)
+            .unwrap()
+    }
+
+    fn hunt(ecs: &mut World, hunter: Entity, target: Entity, last_seen: Point) {
+        ecs.write_storage::<Awareness>().insert(hunter, Awareness{ state: AwarenessState::Hunting{ target, last_seen } }).unwrap();
+    }
+
+    fn think(ecs: &mut World, monster: Entity) -> AwarenessState {
+        VisibilitySystem{}.run_now(ecs);
+        MonsterAI{}.run_now(ecs);
+        ecs.read_storage::<Awareness>().get(monster).unwrap().state
+    }
+
+    #[test]
+    fn lost_target_switches_to_hostile_in_view() {
+        let (mut ecs, player) = world();
+        let orc = spawn(&mut ecs, spawner::orc, 5, 5);
+        let goblin = spawn(&mut ecs, spawner::goblin, 8, 5);
+        hunt(&mut ecs, orc, player, Point::new(35, 35));
+
+        assert_eq!(think(&mut ecs, orc), AwarenessState::Hunting{ target: goblin, last_seen: Point::new(8, 5) });
+    }
+
+    #[test]
+    fn lost_target_is_looked_for_where_last_seen() {
+        let (mut ecs, player) = world();
+        let orc = spawn(&mut ecs, spawner::orc, 5, 5);
+        *ecs.write_storage::<Position>().get_mut(player).unwrap() = Position{ x: 8, y: 5 };
+        *ecs.write_resource::<Point>() = Point::new(8, 5);
+        hunt(&mut ecs, orc, player, Point::new(8, 5));
+        assert_eq!(think(&mut ecs, orc), AwarenessState::Hunting{ target: player, last_seen: Point::new(8, 5) });
+
+        // Slips away while the orc watches, then on out of sight
+        *ecs.write_storage::<Position>().get_mut(player).unwrap() = Position{ x: 10, y: 5 };
+        ecs.write_storage::<Viewshed>().get_mut(player).unwrap().dirty = true;
+        assert_eq!(think(&mut ecs, orc), AwarenessState::Hunting{ target: player, last_seen: Point::new(10, 5) });
+
+        *ecs.write_storage::<Position>().get_mut(player).unwrap() = Position{ x: 35, y: 35 };
+        ecs.write_storage::<Viewshed>().get_mut(player).unwrap().dirty = true;
+        assert_eq!(think(&mut ecs, orc), AwarenessState::Investigating{ x: 10, y: 5 });
+    }
+
+    #[test]
+    fn dead_target_is_forgotten() {
+        let (mut ecs, _player) = world();
+        let orc = spawn(&mut ecs, spawner::orc, 5, 5);
+        let goblin = spawn(&mut ecs, spawner::goblin, 20, 20);
+        ecs.write_storage::<CombatStats>().get_mut(goblin).unwrap().hp = 0;
+        hunt(&mut ecs, orc, goblin, Point::new(20, 20));
+
+        assert_eq!(think(&mut ecs, orc), AwarenessState::Unaware);
+    }
+
+    #[test]
+    fn friends_and_neutrals_are_not_targets() {
+        let (mut ecs, player) = world();
+        let orc = spawn(&mut ecs, spawner::orc, 5, 5);
+        spawn(&mut ecs, spawner::orc, 7, 5);
+        spawn(&mut ecs, spawner::giant_rat, 5, 7);
+        hunt(&mut ecs, orc, player, Point::new(30, 30));
+
+        assert_eq!(think(&mut ecs, orc), AwarenessState::Investigating{ x: 30, y: 30 });
+    }
+
+    #[test]
+    fn cutpurse_leaves_worn_gear_alone() {
+        let (mut ecs, player) = world();
+        ecs.write_storage::<InBackpack>().clear();
+        let sword = ecs.create_entity().with(InBackpack{ owner: player })
+            .with(Equipped{ owner: player, slot: crate::EquipmentSlot::Melee }).build();
+        let cutpurse = spawn(&mut ecs, spawner::goblin_cutpurse, 34, 35);
+        hunt(&mut ecs, cutpurse, player, Point::new(35, 35));
+
+        think(&mut ecs, cutpurse);
+        assert!(!ecs.read_storage::<WantsToUseAbility>().contains(cutpurse));
+
+        ecs.write_storage::<Equipped>().remove(sword);
+        think(&mut ecs, cutpurse);
+        let wants = ecs.read_storage::<WantsToUseAbility>();
+        assert!(wants.get(cutpurse).is_some_and(|wants| matches!(wants.kind, AbilityKind::Steal)));
     }
 }
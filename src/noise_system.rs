@@ -0,0 +1,93 @@
+use std::collections::{BinaryHeap, HashMap};
+use specs::prelude::*;
+use bracket_lib::prelude::RandomNumberGenerator;
+use super::{Map, TileType, Awareness, AwarenessState, Perception};
+
+/// Difficulty for a sleeping monster to be woken by a noise, before loudness is added.
+const WAKE_DC: i32 = 20;
+/// Difficulty for an awake monster to notice a noise, before loudness is added.
+const HEAR_DC: i32 = 10;
+/// Extra loudness lost passing through a door.
+const DOOR_DAMPING: i32 = 2;
+
+pub struct Noise {
+    pub x: i32,
+    pub y: i32,
+    pub volume: i32
+}
+
+/// Noises made this turn, waiting to be heard.
+#[derive(Default)]
+pub struct NoiseEvents {
+    pub noises: Vec<Noise>
+}
+
+impl NoiseEvents {
+    pub fn make_noise(&mut self, x: i32, y: i32, volume: i32) {
+        self.noises.push(Noise{ x, y, volume });
+    }
+}
+
+/// How loud `noise` is on each tile it reaches. Sound loses one point per tile travelled,
+/// can't pass through walls, and is muffled by doors.
+fn spread_noise(map: &Map, noise: &Noise) -> HashMap<usize, i32> {
+    let mut loudness: HashMap<usize, i32> = HashMap::new();
+    let mut open: BinaryHeap<(i32, i32, i32)> = BinaryHeap::new();
+    open.push((noise.volume, noise.x, noise.y));
+
+    while let Some((volume, x, y)) = open.pop() {
+        let idx = map.xy_idx(x, y);
+        if loudness.get(&idx).is_some_and(|v| *v >= volume) { continue; }
+        loudness.insert(idx, volume);
+
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || nx >= map.width || ny < 0 || ny >= map.height { continue; }
+
+            let next_volume = match map.tiles[map.xy_idx(nx, ny)] {
+                TileType::Floor => volume - 1,
+                TileType::Door => volume - 1 - DOOR_DAMPING,
+                TileType::Wall | TileType::SecretDoor => continue
+            };
+            if next_volume > 0 {
+                open.push((next_volume, nx, ny));
+            }
+        }
+    }
+
+    loudness
+}
+
+/// Lets monsters hear this turn's noises. Those that do go to investigate.
+pub struct NoiseSystem {}
+
+impl<'a> System<'a> for NoiseSystem {
+    type SystemData = ( ReadExpect<'a, Map>,
+                        WriteExpect<'a, NoiseEvents>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, Perception>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, mut events, mut rng, mut awareness, perception) = data;
+
+        for noise in events.noises.drain(..) {
+            for (idx, loudness) in spread_noise(&map, &noise) {
+                for listener in map.entities_at(idx).iter() {
+                    if let Some(aware) = awareness.get_mut(*listener) {
+                        let dc = match aware.state {
+                            AwarenessState::Hunting{..} => continue,
+                            AwarenessState::Asleep => WAKE_DC,
+                            AwarenessState::Unaware | AwarenessState::Investigating{..} => HEAR_DC
+                        };
+                        let bonus = perception.get(*listener).map_or(0, |p| p.value);
+                        if rng.roll_dice(1, 20) + bonus + loudness >= dc {
+                            aware.state = AwarenessState::Investigating{ x: noise.x, y: noise.y };
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
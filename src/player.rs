@@ -1,10 +1,33 @@
 use bracket_lib::prelude::{BTerm, Point, VirtualKeyCode};
 use specs::prelude::*;
 
-use super::{State, GameLog, Position, Item, Player, Map, Viewshed, RunState, CombatStats, WantsToMelee, WantsToPickupItem};
-use std::cmp::{max, min};
+use super::{State, GameLog, Position, Item, Player, Map, TileType, Viewshed, RunState, CombatStats, WantsToMelee, WantsToPickupItem, Perception,
+    InBackpack, RangedWeapon, Ammunition, ranged_weapon_of, ammunition_of, gui, StatusEffects, StatusKind,
+    Faction, Reaction, Reactions, Resting, Name};
+use super::NoiseEvents;
+use bracket_lib::prelude::RandomNumberGenerator;
+
+/// How far footsteps carry.
+const FOOTSTEP_VOLUME: i32 = 2;
+/// How far the creak of a door carries.
+const DOOR_VOLUME: i32 = 6;
 
 fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+    let (mut delta_x, mut delta_y) = (delta_x, delta_y);
+    {
+        // Half the time, confusion sends you somewhere you didn't mean to go
+        let player_entity = ecs.fetch::<Entity>();
+        let statuses = ecs.read_storage::<StatusEffects>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        if statuses.get(*player_entity).is_some_and(|s| s.has(StatusKind::Confused)) && rng.roll_dice(1, 2) == 1 {
+            (delta_x, delta_y) = match rng.roll_dice(1, 8) {
+                1 => (-1, -1), 2 => (0, -1), 3 => (1, -1), 4 => (-1, 0),
+                5 => (1, 0), 6 => (-1, 1), 7 => (0, 1), _ => (1, 1)
+            };
+            ecs.fetch_mut::<GameLog>().entries.push("You stumble around in confusion.".to_string());
+        }
+    }
+
     let mut positions = ecs.write_storage::<Position>();
     let players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
@@ -13,34 +36,150 @@ fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let combat_stats = ecs.read_storage::<CombatStats>();
     let map = ecs.fetch::<Map>();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let factions = ecs.read_storage::<Faction>();
+    let reactions = ecs.fetch::<Reactions>();
+    let mut swap: Option<(Entity, Position)> = None;
 
     for (entity, _player, pos, viewshed) in (&entities, &players, &mut positions, &mut viewsheds).join() {
         if pos.x + delta_x < 1 || pos.x + delta_x > map.width - 1 || pos.y + delta_y < 1 || pos.y + delta_y > map.height - 1 { return; }
 
         let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
 
-        for potential_target in map.tile_content[destination_idx].iter() {
+        let mut friend = None;
+        for potential_target in map.entities_at(destination_idx).iter() {
             let target = combat_stats.get(*potential_target);
 
             if let Some(_target) = target {
+                // Allies get out of the way rather than being attacked
+                let friendly = match (factions.get(entity), factions.get(*potential_target)) {
+                    (Some(ours), Some(theirs)) => reactions.reaction(*ours, *theirs) == Reaction::Friendly,
+                    _ => false
+                };
+                if friendly {
+                    friend = Some(*potential_target);
+                    continue;
+                }
                 wants_to_melee.insert(entity, WantsToMelee{ target: *potential_target }).expect("Add target failed");
                 return;
             }
         }
-        if !map.blocked[destination_idx] {
-            pos.x = min(79, max (0, pos.x + delta_x));
-            pos.y = min(79, max (0, pos.y + delta_y));
+        if let Some(friend) = friend {
+            swap = Some((friend, Position{ x: pos.x, y: pos.y }));
+        }
+        if !map.blocked[destination_idx] || friend.is_some() {
+            pos.x = (pos.x + delta_x).clamp(0, 79);
+            pos.y = (pos.y + delta_y).clamp(0, 79);
 
             viewshed.dirty = true;
             let mut player_pos = ecs.write_resource::<Point>();
 
             player_pos.x = pos.x;
             player_pos.y = pos.y;
+
+            let mut noise = ecs.write_resource::<NoiseEvents>();
+            if map.tiles[destination_idx] == TileType::Door {
+                noise.make_noise(pos.x, pos.y, DOOR_VOLUME);
+            } else {
+                noise.make_noise(pos.x, pos.y, FOOTSTEP_VOLUME);
+            }
+        }
+    }
+
+    if let Some((friend, to)) = swap {
+        if let Some(pos) = positions.get_mut(friend) {
+            *pos = to;
+        }
+        if let Some(viewshed) = viewsheds.get_mut(friend) {
+            viewshed.dirty = true;
+        }
+    }
+}
+
+/// The name of something hostile to the player that they can see, if there is anything.
+fn hostile_in_view(ecs: &World) -> Option<String> {
+    let player_entity = ecs.fetch::<Entity>();
+    let map = ecs.fetch::<Map>();
+    let reactions = ecs.fetch::<Reactions>();
+    let entities = ecs.entities();
+    let factions = ecs.read_storage::<Faction>();
+    let positions = ecs.read_storage::<Position>();
+    let names = ecs.read_storage::<Name>();
+    let own = *factions.get(*player_entity)?;
+
+    (&entities, &factions, &positions, &names).join()
+        .find(|(entity, faction, pos, _name)| *entity != *player_entity
+            && reactions.reaction(own, **faction) == Reaction::Hostile
+            && map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+        .map(|(_entity, _faction, _pos, name)| name.name.clone())
+}
+
+/// Starts passing turns until the player is healed, if nothing's around to stop them.
+fn start_resting(ecs: &mut World) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let hp = match ecs.read_storage::<CombatStats>().get(player_entity) {
+        Some(stats) if stats.hp < stats.max_hp => stats.hp,
+        _ => {
+            ecs.fetch_mut::<GameLog>().entries.push("You are already at full health.".to_string());
+            return RunState::AwaitingInput;
+        }
+    };
+    if hostile_in_view(ecs).is_some() {
+        ecs.fetch_mut::<GameLog>().entries.push("You can't rest with enemies in view.".to_string());
+        return RunState::AwaitingInput;
+    }
+
+    ecs.fetch_mut::<GameLog>().entries.push("You settle down to rest.".to_string());
+    ecs.write_storage::<Resting>().insert(player_entity, Resting{ hp }).expect("Unable to insert resting");
+    RunState::PlayerTurn
+}
+
+/// Passes another turn while resting, unless something should stop it. Returns `None` once
+/// the player has stopped.
+fn keep_resting(ecs: &mut World, ctx: &BTerm) -> Option<RunState> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let resting_hp = ecs.read_storage::<Resting>().get(player_entity)?.hp;
+    let (hp, max_hp) = ecs.read_storage::<CombatStats>().get(player_entity).map_or((0, 0), |stats| (stats.hp, stats.max_hp));
+
+    let interruption = if ctx.key.is_some() {
+        Some("You stop resting.".to_string())
+    } else if hp < resting_hp {
+        Some("You are interrupted!".to_string())
+    } else if let Some(name) = hostile_in_view(ecs) {
+        Some(format!("You stop resting: {} comes into view.", name))
+    } else if hp >= max_hp {
+        Some("You feel rested.".to_string())
+    } else {
+        None
+    };
+
+    match interruption {
+        Some(message) => {
+            ecs.fetch_mut::<GameLog>().entries.push(message);
+            ecs.write_storage::<Resting>().remove(player_entity);
+            // Whatever key stopped the rest doesn't also count as a move
+            Some(RunState::AwaitingInput)
+        }
+        None => {
+            ecs.write_storage::<Resting>().insert(player_entity, Resting{ hp }).expect("Unable to insert resting");
+            Some(RunState::PlayerTurn)
         }
     }
 }
 
 pub fn player_input(game_state: &mut State, ctx: &mut BTerm) -> RunState {
+    // Sleep passes the turns by without asking
+    {
+        let player_entity = game_state.ecs.fetch::<Entity>();
+        let statuses = game_state.ecs.read_storage::<StatusEffects>();
+        if statuses.get(*player_entity).is_some_and(|s| s.has(StatusKind::Asleep)) {
+            return RunState::PlayerTurn;
+        }
+    }
+    // So does resting, until something interrupts it
+    if let Some(runstate) = keep_resting(&mut game_state.ecs, ctx) {
+        return runstate;
+    }
+
     match ctx.key {
         None => { return RunState::AwaitingInput} // Nothing happened
         Some(key) => match key {
@@ -69,8 +208,17 @@ pub fn player_input(game_state: &mut State, ctx: &mut BTerm) -> RunState {
             
             // Actions
             VirtualKeyCode::G => get_item(&mut game_state.ecs),
+            VirtualKeyCode::S => search(&mut game_state.ecs),
             VirtualKeyCode::I => return RunState::ShowInventory,
             VirtualKeyCode::D => return RunState::ShowDropItem,
+            VirtualKeyCode::C => return RunState::ShowCharacter,
+            VirtualKeyCode::F => return start_targeting(&mut game_state.ecs),
+            VirtualKeyCode::Z => return start_resting(&mut game_state.ecs),
+
+            // Wait a turn
+            VirtualKeyCode::Period |
+            VirtualKeyCode::Space |
+            VirtualKeyCode::Numpad5 => {}
             _ => { return RunState::AwaitingInput}
         },
     }
@@ -100,3 +248,65 @@ fn get_item(ecs: &mut World) {
         }
     }
 }
+
+/// Opens the targeting view if the player has something to shoot with, aiming at the nearest
+/// enemy to begin with.
+fn start_targeting(ecs: &mut World) -> RunState {
+    {
+        let player_entity = ecs.fetch::<Entity>();
+        let entities = ecs.entities();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let weapons = ecs.read_storage::<RangedWeapon>();
+        let ammunition = ecs.read_storage::<Ammunition>();
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+        if ranged_weapon_of(*player_entity, &entities, &backpack, &weapons).is_none() {
+            gamelog.entries.push("You have nothing to shoot with.".to_string());
+            return RunState::AwaitingInput;
+        }
+        if ammunition_of(*player_entity, &entities, &backpack, &ammunition).is_none() {
+            gamelog.entries.push("You are out of ammunition.".to_string());
+            return RunState::AwaitingInput;
+        }
+    }
+
+    RunState::ShowTargeting{ target: gui::ranged_targets(ecs).first().copied() }
+}
+
+/// Difficulty for spotting a secret door with a d20 + perception roll.
+const SEARCH_DC: i32 = 15;
+
+/// Checks every tile around the player for secret doors, each one with its own perception roll.
+fn search(ecs: &mut World) {
+    let player_pos = ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let perception = ecs.read_storage::<Perception>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let mut map = ecs.fetch_mut::<Map>();
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let bonus = perception.get(*player_entity).map_or(0, |p| p.value);
+    let mut found = false;
+
+    for y in player_pos.y - 1 ..= player_pos.y + 1 {
+        for x in player_pos.x - 1 ..= player_pos.x + 1 {
+            if x < 0 || x >= map.width || y < 0 || y >= map.height { continue; }
+
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::SecretDoor && rng.roll_dice(1, 20) + bonus >= SEARCH_DC {
+                map.discover_secret_door(idx);
+                found = true;
+            }
+        }
+    }
+
+    if found {
+        gamelog.entries.push("You discover a secret door!".to_string());
+        if let Some(viewshed) = viewsheds.get_mut(*player_entity) {
+            viewshed.dirty = true;
+        }
+    } else {
+        gamelog.entries.push("You search the area but find nothing.".to_string());
+    }
+}
@@ -0,0 +1,153 @@
+use std::ops::Deref;
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use bracket_lib::prelude::{BaseMap, Point, RandomNumberGenerator};
+use super::{AttackRoll, roll_attack, roll_damage, CombatStats, WantsToShoot, RangedWeapon, Ammunition, InBackpack, Name,
+    SufferDamage, gamelog::GameLog, Position, Awareness, AwarenessState, NoiseEvents, Map, Equipped, DefenseBonus, defense_bonus};
+
+/// A bowstring is a lot quieter than a brawl.
+const SHOT_VOLUME: i32 = 4;
+
+/// The first ranged weapon in `owner`'s backpack, if they carry one.
+pub fn ranged_weapon_of<B, W>(owner: Entity, entities: &Entities, backpack: &Storage<InBackpack, B>, weapons: &Storage<RangedWeapon, W>)
+    -> Option<RangedWeapon>
+    where B: Deref<Target = MaskedStorage<InBackpack>>, W: Deref<Target = MaskedStorage<RangedWeapon>>
+{
+    (entities, backpack, weapons).join()
+        .find(|(_entity, pack, _weapon)| pack.owner == owner)
+        .map(|(_entity, _pack, weapon)| *weapon)
+}
+
+/// The first stack of ammunition in `owner`'s backpack that isn't empty.
+pub fn ammunition_of<B, A>(owner: Entity, entities: &Entities, backpack: &Storage<InBackpack, B>, ammunition: &Storage<Ammunition, A>)
+    -> Option<Entity>
+    where B: Deref<Target = MaskedStorage<InBackpack>>, A: Deref<Target = MaskedStorage<Ammunition>>
+{
+    (entities, backpack, ammunition).join()
+        .find(|(_entity, pack, ammo)| pack.owner == owner && ammo.count > 0)
+        .map(|(entity, _pack, _ammo)| entity)
+}
+
+/// Fires shooters' projectiles along a Bresenham line towards their targets. The shot hits the
+/// first creature in its path, whoever that is, and stops at walls or once it runs out of range.
+pub struct RangedCombatSystem {}
+
+impl<'a> System<'a> for RangedCombatSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, NoiseEvents>,
+                        WriteStorage<'a, WantsToShoot>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, InBackpack>,
+                        ReadStorage<'a, RangedWeapon>,
+                        WriteStorage<'a, Ammunition>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, Awareness>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, DefenseBonus>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut log, mut rng, map, mut noise, mut wants_shoot, names, combat_stats, positions, backpack,
+            weapons, mut ammunition, mut inflict_damage, mut awareness, equipped, defense_bonuses) = data;
+
+        let mut spent: Vec<Entity> = Vec::new();
+
+        for (entity, shot, name, stats, pos) in (&entities, &wants_shoot, &names, &combat_stats, &positions).join() {
+            if stats.hp <= 0 { continue; }
+
+            let weapon = match ranged_weapon_of(entity, &entities, &backpack, &weapons) {
+                Some(weapon) => weapon,
+                None => continue
+            };
+            // Shots out of sight are only heard about
+            let seen = map.visible_tiles[map.xy_idx(pos.x, pos.y)];
+            let ammo = match ammunition_of(entity, &entities, &backpack, &ammunition) {
+                Some(ammo) => ammo,
+                None => {
+                    if seen {
+                        log.entries.push(format!("{} has nothing left to shoot.", &name.name));
+                    }
+                    continue;
+                }
+            };
+            let target_pos = match positions.get(shot.target) {
+                Some(target_pos) => Point::new(target_pos.x, target_pos.y),
+                None => continue
+            };
+
+            if let Some(quiver) = ammunition.get_mut(ammo) {
+                quiver.count -= 1;
+                if quiver.count <= 0 { spent.push(ammo); }
+            }
+            noise.make_noise(pos.x, pos.y, SHOT_VOLUME);
+
+            let mut path = map.projectile_path(Point::new(pos.x, pos.y), target_pos);
+            path.truncate(weapon.range as usize);
+
+            let victim = path.iter()
+                .flat_map(|point| map.entities_at(map.xy_idx(point.x, point.y)).iter())
+                .find(|other| **other != entity && combat_stats.get(**other).is_some_and(|s| s.hp > 0))
+                .copied();
+
+            let victim = match victim {
+                Some(victim) => victim,
+                None => {
+                    let hit_wall = path.last().is_some_and(|end| map.is_opaque(map.xy_idx(end.x, end.y)));
+                    if seen {
+                        if hit_wall {
+                            log.entries.push(format!("{}'s shot hits the wall.", &name.name));
+                        } else {
+                            log.entries.push(format!("{}'s shot falls short.", &name.name));
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(aware) = awareness.get_mut(victim) {
+                aware.state = AwarenessState::Hunting{ target: entity, last_seen: Point::new(pos.x, pos.y) };
+            }
+
+            let seen = seen || positions.get(victim).is_some_and(|at| map.visible_tiles[map.xy_idx(at.x, at.y)]);
+            let victim_name = &names.get(victim).unwrap().name;
+            let victim_stats = combat_stats.get(victim).unwrap();
+            let defense = victim_stats.defense + defense_bonus(victim, &equipped, &defense_bonuses);
+            match roll_attack(&mut rng, stats.power, defense) {
+                AttackRoll::Fumble => {
+                    if seen {
+                        log.entries.push(format!("{} fumbles a shot at {}.", &name.name, victim_name));
+                    }
+                }
+                AttackRoll::Miss => {
+                    if seen {
+                        log.entries.push(format!("{}'s shot misses {}.", &name.name, victim_name));
+                    }
+                }
+                roll => {
+                    let critical = roll == AttackRoll::Critical;
+                    let damage = roll_damage(&mut rng, weapon.n_dice, weapon.die_type, weapon.bonus, critical);
+
+                    if seen {
+                        if critical {
+                            log.entries.push(format!("{} shoots {} clean through, for {} {} damage!", &name.name, victim_name, damage, weapon.kind.name()));
+                        } else {
+                            log.entries.push(format!("{} shoots {}, for {} {} damage.", &name.name, victim_name, damage, weapon.kind.name()));
+                        }
+                    }
+                    SufferDamage::new_damage(&mut inflict_damage, victim, damage, weapon.kind);
+                }
+            }
+        }
+
+        for ammo in spent {
+            entities.delete(ammo).expect("Delete failed");
+        }
+        wants_shoot.clear();
+    }
+}
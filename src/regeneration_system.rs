@@ -0,0 +1,29 @@
+use specs::prelude::*;
+use super::{CombatStats, Undead, GameTurn, RunState};
+
+/// Game turns between each hit point creatures get back on their own.
+const REGEN_INTERVAL: i32 = 8;
+
+/// Slowly heals every living creature as the turns go by. The undead don't heal.
+pub struct RegenerationSystem {}
+
+impl<'a> System<'a> for RegenerationSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, GameTurn>,
+                        ReadExpect<'a, RunState>,
+                        WriteStorage<'a, CombatStats>,
+                        ReadStorage<'a, Undead>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (turn, runstate, mut combat_stats, undead) = data;
+
+        if *runstate != RunState::MonsterTurn || turn.number % REGEN_INTERVAL != 0 { return; }
+
+        for (stats, _undead) in (&mut combat_stats, !&undead).join() {
+            if stats.hp > 0 {
+                stats.hp = i32::min(stats.max_hp, stats.hp + 1);
+            }
+        }
+    }
+}
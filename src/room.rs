@@ -0,0 +1,187 @@
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+use super::Rect;
+
+/// What a room is for. Generators pick one per room and the spawner fills the room to match.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum RoomTheme {
+    Ordinary,
+    Empty,
+    TreasureVault,
+    Barracks,
+    Library,
+    Shrine
+}
+
+impl RoomTheme {
+    /// Rolls a theme for a regular room. Treasure vaults are only ever placed deliberately by
+    /// the generator, so they never come out of this.
+    pub fn roll(rng: &mut RandomNumberGenerator) -> RoomTheme {
+        match rng.roll_dice(1, 100) {
+            1..=10 => RoomTheme::Barracks,
+            11..=18 => RoomTheme::Library,
+            19..=24 => RoomTheme::Shrine,
+            25..=34 => RoomTheme::Empty,
+            _ => RoomTheme::Ordinary
+        }
+    }
+
+    /// Short label used in tooltips, if the theme is worth mentioning.
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            RoomTheme::Ordinary | RoomTheme::Empty => None,
+            RoomTheme::TreasureVault => Some("Treasure vault"),
+            RoomTheme::Barracks => Some("Barracks"),
+            RoomTheme::Library => Some("Library"),
+            RoomTheme::Shrine => Some("Shrine")
+        }
+    }
+
+    /// Message shown on arrival if a room of this theme is somewhere on the level.
+    pub fn feeling(&self) -> Option<&'static str> {
+        match self {
+            RoomTheme::Ordinary | RoomTheme::Empty => None,
+            RoomTheme::TreasureVault => Some("You sense great treasure nearby."),
+            RoomTheme::Barracks => Some("You hear the clatter of armour."),
+            RoomTheme::Library => Some("You smell old parchment."),
+            RoomTheme::Shrine => Some("You feel a holy presence.")
+        }
+    }
+}
+
+/// The outline of a room's floor inside its bounding `Rect`.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum RoomShape {
+    Rectangle,
+    Circle,
+    Cross,
+    LShaped,
+    /// A rectangle with a ragged, randomly eroded edge.
+    Cavern,
+    /// A rectangle with a grid of wall pillars inside it.
+    Pillared
+}
+
+impl RoomShape {
+    pub fn roll(rng: &mut RandomNumberGenerator) -> RoomShape {
+        match rng.roll_dice(1, 12) {
+            1..=5 => RoomShape::Rectangle,
+            6..=7 => RoomShape::Circle,
+            8 => RoomShape::Cross,
+            9 => RoomShape::LShaped,
+            10..=11 => RoomShape::Cavern,
+            _ => RoomShape::Pillared
+        }
+    }
+}
+
+pub struct Room {
+    /// Bounding box, using the same convention as before shapes existed: the floor never
+    /// goes outside `x1+1..=x2`, `y1+1..=y2`.
+    pub rect: Rect,
+    pub shape: RoomShape,
+    pub theme: RoomTheme,
+    floor: Vec<(i32, i32)>,
+    center: (i32, i32)
+}
+
+impl Room {
+    /// Lays out the floor for `shape` inside `rect`. The room's centre is always one of its
+    /// floor tiles, so corridors and the player start can rely on it.
+    pub fn new(rect: Rect, shape: RoomShape, theme: RoomTheme, rng: &mut RandomNumberGenerator) -> Room {
+        let width = rect.x2 - rect.x1;
+        let height = rect.y2 - rect.y1;
+        let (cx, cy) = rect.center();
+        // Local coordinates run from (0, 0) at the top-left floor tile
+        let (lcx, lcy) = (cx - rect.x1 - 1, cy - rect.y1 - 1);
+        let mut center = (cx, cy);
+
+        let mut floor = Vec::new();
+        match shape {
+            RoomShape::Rectangle => {
+                for ly in 0..height {
+                    for lx in 0..width { floor.push((lx, ly)); }
+                }
+            }
+            RoomShape::Circle => {
+                let radius = i32::min(width, height) as f32 / 2.0;
+                for ly in 0..height {
+                    for lx in 0..width {
+                        let distance = bracket_lib::geometry::DistanceAlg::Pythagoras
+                            .distance2d(Point::new(lcx, lcy), Point::new(lx, ly));
+                        if distance <= radius { floor.push((lx, ly)); }
+                    }
+                }
+            }
+            RoomShape::Cross => {
+                let arm_x = i32::max(1, width / 6);
+                let arm_y = i32::max(1, height / 6);
+                for ly in 0..height {
+                    for lx in 0..width {
+                        if i32::abs(lx - lcx) <= arm_x || i32::abs(ly - lcy) <= arm_y { floor.push((lx, ly)); }
+                    }
+                }
+            }
+            RoomShape::LShaped => {
+                // Built as a vertical arm down the left and a horizontal arm along the bottom,
+                // then mirrored at random so the corner can end up anywhere.
+                let thick_x = i32::max(2, width / 2);
+                let thick_y = i32::max(2, height / 2);
+                let flip_x = rng.range(0, 2) == 1;
+                let flip_y = rng.range(0, 2) == 1;
+                let mirror = |lx: i32, ly: i32| {
+                    (if flip_x { width - 1 - lx } else { lx }, if flip_y { height - 1 - ly } else { ly })
+                };
+
+                for ly in 0..height {
+                    for lx in 0..width {
+                        if lx < thick_x || ly >= height - thick_y { floor.push(mirror(lx, ly)); }
+                    }
+                }
+                let (ax, ay) = mirror(thick_x / 2, height - 1 - thick_y / 2);
+                center = (rect.x1 + 1 + ax, rect.y1 + 1 + ay);
+            }
+            RoomShape::Cavern => {
+                // Only the outermost ring is eroded. Everything inside it stays floor, so every
+                // surviving edge tile still touches the rest of the room.
+                for ly in 0..height {
+                    for lx in 0..width {
+                        let edge = lx == 0 || ly == 0 || lx == width - 1 || ly == height - 1;
+                        if !edge || rng.roll_dice(1, 10) > 4 { floor.push((lx, ly)); }
+                    }
+                }
+            }
+            RoomShape::Pillared => {
+                for ly in 0..height {
+                    for lx in 0..width {
+                        let pillar = lx % 2 == 1 && ly % 2 == 1 && lx < width - 1 && ly < height - 1
+                            && (lx, ly) != (lcx, lcy);
+                        if !pillar { floor.push((lx, ly)); }
+                    }
+                }
+            }
+        }
+
+        let floor = floor.into_iter().map(|(lx, ly)| (rect.x1 + 1 + lx, rect.y1 + 1 + ly)).collect();
+        Room{ rect, shape, theme, floor, center }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        self.center
+    }
+
+    pub fn floor_tiles(&self) -> &[(i32, i32)] {
+        &self.floor
+    }
+
+    /// True if (x, y) is on the room's floor.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x > self.rect.x1 && x <= self.rect.x2 && y > self.rect.y1 && y <= self.rect.y2
+            && self.floor.contains(&(x, y))
+    }
+
+    /// Rooms are tested for overlap by their bounding boxes, so any two shapes that pass can be
+    /// carved without touching each other.
+    pub fn intersect(&self, other: &Rect) -> bool {
+        self.rect.intersect(other)
+    }
+}
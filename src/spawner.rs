@@ -1,10 +1,13 @@
-use bracket_lib::prelude::{RGB, RandomNumberGenerator};
+use bracket_lib::prelude::{Point, RGB, RandomNumberGenerator};
 use specs::prelude::*;
-use super::{CombatStats, Rect, MAPWIDTH, Player, Item, Potion, Renderable, Name, Position, Viewshed, Monster, BlocksTile};
+use super::{CombatStats, MeleeDamage, DamageType, Resistances, Room, RoomTheme, MAPWIDTH, Player, Item, Potion, Scroll, Ranged, Consumable, Unidentified, Identifies, ItemAppearances, ProvidesHealing, InflictsDamage, AreaOfEffect, Teleports, GrantsTelepathy, GrantsItemDetection, GrantsMagicMapping, CausesAmnesia, Renderable, Name, Position, Viewshed, Monster, BlocksTile, Perception,
+    Awareness, AwarenessState, Stealth, RangedWeapon, Ammunition, InBackpack,
+    GrantsStatus, StatusKind, LootItem, LootTable, Corpse, Undead, KnocksBack, Faction,
+    Ability, AbilityKind, Abilities, Map, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus};
 
 // Spawns player and returns their entity object.
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
-    ecs
+    let player = ecs
         .create_entity()
         .with(Position { x: player_x, y: player_y})
         .with(Renderable {
@@ -17,62 +20,285 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
         .with(Viewshed{ visible_tiles: Vec::new(), range: 8, dirty: true })
         .with(Name{name: "Player".to_string()})
         .with(CombatStats{ max_hp: 30, hp: 30, defense: 2, power: 5})
-        .build()
+        .with(MeleeDamage{ n_dice: 1, die_type: 6, bonus: 1, kind: DamageType::Slashing })
+        .with(Perception{ value: 2 })
+        .with(Stealth{ value: 2 })
+        .with(Faction::Player)
+        .build();
+
+    let bow = shortbow(ecs);
+    ecs.write_storage::<InBackpack>().insert(bow, InBackpack{ owner: player }).expect("Unable to insert backpack entry");
+    let arrows = arrows(ecs, 12);
+    ecs.write_storage::<InBackpack>().insert(arrows, InBackpack{ owner: player }).expect("Unable to insert backpack entry");
+
+    player
 }
 
 const MAX_MONSTERS: i32 = 4;
 const MAX_ITEMS: i32 = 2;
 
-pub fn spawn_room(ecs: &mut World, room: &Rect) {
-    let mut monster_spawn_points: Vec<usize> = Vec::new();
-    let mut potion_spawn_points: Vec<usize> = Vec::new();
+/// Fills a room according to its theme.
+pub fn spawn_room(ecs: &mut World, room: &Room) {
+    match room.theme {
+        RoomTheme::Ordinary => spawn_ordinary(ecs, room),
+        RoomTheme::Empty => {}
+        RoomTheme::TreasureVault => spawn_vault(ecs, room),
+        RoomTheme::Barracks => spawn_barracks(ecs, room),
+        RoomTheme::Library => spawn_library(ecs, room),
+        RoomTheme::Shrine => spawn_shrine(ecs, room),
+    }
+}
 
-    // Scope to keep the borrow checker happy
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_monsters = rng.roll_dice(1, MAX_MONSTERS + 2) - 3;
-        let num_potions = rng.roll_dice(1, MAX_ITEMS + 2) - 3;
-
-        for _i in 0..num_monsters {
-            let mut added = false;
-            while !added {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAPWIDTH) + x;
-                if !monster_spawn_points.contains(&idx) {
-                    monster_spawn_points.push(idx);
-                    added = true;
-                }
-            }
+/// Picks up to `count` distinct floor tiles inside `room`, returned as map indices.
+fn random_spawn_points(ecs: &mut World, room: &Room, count: i32) -> Vec<usize> {
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    let floor = room.floor_tiles();
+    let count = i32::min(count, floor.len() as i32);
+    let mut spawn_points: Vec<usize> = Vec::new();
+
+    while (spawn_points.len() as i32) < count {
+        let (x, y) = floor[rng.range(0, floor.len() as i32) as usize];
+        let idx = (y as usize * MAPWIDTH) + x as usize;
+        if !spawn_points.contains(&idx) {
+            spawn_points.push(idx);
+        }
+    }
+
+    spawn_points
+}
+
+fn roll(ecs: &mut World, n: i32, die_type: i32) -> i32 {
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    rng.roll_dice(n, die_type)
+}
+
+fn spawn_ordinary(ecs: &mut World, room: &Room) {
+    let num_monsters = roll(ecs, 1, MAX_MONSTERS + 2) - 3;
+    let num_potions = roll(ecs, 1, MAX_ITEMS + 2) - 3;
+
+    for idx in random_spawn_points(ecs, room, num_monsters).iter() {
+        random_monster(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+    }
+    for idx in random_spawn_points(ecs, room, num_potions).iter() {
+        random_item(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+    }
+}
+
+const MIN_VAULT_ITEMS: i32 = 2;
+const MAX_VAULT_ITEMS: i32 = 4;
+
+/// Vaults are hidden behind secret doors, so they are always stocked and never guarded. There's
+/// always at least one piece of equipment, and the rest is drawn from the vault's own pool.
+fn spawn_vault(ecs: &mut World, vault: &Room) {
+    let num_items = MIN_VAULT_ITEMS - 1 + roll(ecs, 1, MAX_VAULT_ITEMS - MIN_VAULT_ITEMS + 1);
+    for (i, idx) in random_spawn_points(ecs, vault, num_items).iter().enumerate() {
+        let (x, y) = ((*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+        if i == 0 {
+            random_equipment(ecs, x, y);
+        } else {
+            random_vault_item(ecs, x, y);
         }
+    }
+}
+
+/// Loot worth finding a secret door for: heavy on equipment and scrolls, and never a potion
+/// nobody would want.
+fn random_vault_item(ecs: &mut World, x: i32, y: i32) {
+    match roll(ecs, 1, 10) {
+        1..=4 => random_equipment(ecs, x, y),
+        5 | 6 => random_scroll(ecs, x, y),
+        7 => status_potion(ecs, x, y, "Potion of Speed", StatusKind::Hasted, 20),
+        8 => status_potion(ecs, x, y, "Potion of Regeneration", StatusKind::Regenerating, 20),
+        9 => magic_mapping_potion(ecs, x, y),
+        _ => health_potion(ecs, x, y)
+    }
+}
 
-        for _i in 0..num_potions {
-            let mut added = false;
-            while !added {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
+/// A crowd of orcs and their bedrolls.
+fn spawn_barracks(ecs: &mut World, room: &Room) {
+    let num_monsters = 1 + roll(ecs, 1, 4);
+    let num_beds = 1 + roll(ecs, 1, 3);
 
-                let idx = (y * MAPWIDTH) + x;
-                if !potion_spawn_points.contains(&idx) {
-                    potion_spawn_points.push(idx);
-                    added = true;
-                }
+    for idx in random_spawn_points(ecs, room, num_beds).iter() {
+        decoration(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32, bracket_lib::prelude::to_cp437('='), "Bedroll");
+    }
+    for idx in random_spawn_points(ecs, room, num_monsters).iter() {
+        orc(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+    }
+}
+
+/// Shelves of books, a lone reader and usually a scroll or two left lying about.
+fn spawn_library(ecs: &mut World, room: &Room) {
+    let num_shelves = 2 + roll(ecs, 1, 4);
+    let num_monsters = roll(ecs, 1, 2) - 1;
+    let num_potions = roll(ecs, 1, 2) - 1;
+    let num_scrolls = roll(ecs, 1, 3) - 1;
+
+    for idx in random_spawn_points(ecs, room, num_shelves).iter() {
+        decoration(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32, bracket_lib::prelude::to_cp437('≡'), "Bookshelf");
+    }
+    for idx in random_spawn_points(ecs, room, num_monsters).iter() {
+        goblin(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+    }
+    for idx in random_spawn_points(ecs, room, num_potions).iter() {
+        random_item(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+    }
+    for idx in random_spawn_points(ecs, room, num_scrolls).iter() {
+        random_scroll(ecs, (*idx % MAPWIDTH) as i32, (*idx / MAPWIDTH) as i32);
+    }
+}
+
+/// An altar in the middle of the room with an offering left on it. Nothing hostile lingers here.
+fn spawn_shrine(ecs: &mut World, room: &Room) {
+    let (x, y) = room.center();
+    decoration(ecs, x, y, bracket_lib::prelude::to_cp437('Ω'), "Altar");
+    health_potion(ecs, x, y);
+}
+
+/// Rolls each entry of a loot table and puts whatever comes up at (x, y).
+pub fn drop_loot(ecs: &mut World, table: &[(LootItem, i32)], x: i32, y: i32) {
+    for (item, chance) in table.iter() {
+        if roll(ecs, 1, 100) > *chance { continue; }
+
+        match item {
+            LootItem::HealthPotion => health_potion(ecs, x, y),
+            LootItem::AnyItem => random_item(ecs, x, y),
+            LootItem::Arrows => {
+                let count = 2 + roll(ecs, 1, 6);
+                let bundle = arrows(ecs, count);
+                ecs.write_storage::<Position>().insert(bundle, Position{x, y}).expect("Unable to insert position");
+            }
+            LootItem::Shortbow => {
+                let bow = shortbow(ecs);
+                ecs.write_storage::<Position>().insert(bow, Position{x, y}).expect("Unable to insert position");
             }
         }
     }
+}
 
-    // Spawn the monsters
-    for idx in monster_spawn_points.iter() {
-        let x = *idx % MAPWIDTH;
-        let y = *idx / MAPWIDTH;
-        random_monster(ecs, x as i32, y as i32);
+pub fn random_item(ecs: &mut World, x: i32, y: i32) {
+    match roll(ecs, 1, 17) {
+        1 => telepathy_potion(ecs, x, y),
+        2 => detection_potion(ecs, x, y),
+        3 => magic_mapping_potion(ecs, x, y),
+        4 => amnesia_potion(ecs, x, y),
+        5 => {
+            let count = 4 + roll(ecs, 2, 4);
+            let bundle = arrows(ecs, count);
+            ecs.write_storage::<Position>().insert(bundle, Position{x, y}).expect("Unable to insert position");
+        }
+        6 => status_potion(ecs, x, y, "Potion of Speed", StatusKind::Hasted, 20),
+        7 => status_potion(ecs, x, y, "Potion of Regeneration", StatusKind::Regenerating, 20),
+        8 => random_bad_potion(ecs, x, y),
+        9 | 10 => random_equipment(ecs, x, y),
+        11 => teleport_potion(ecs, x, y),
+        12 | 13 => random_scroll(ecs, x, y),
+        _ => health_potion(ecs, x, y)
     }
+}
 
-    // Spawn the potions
-    for idx in potion_spawn_points.iter() {
-        let x = *idx % MAPWIDTH;
-        let y = *idx / MAPWIDTH;
-        health_potion(ecs, x as i32, y as i32);
+/// Something to wear or wield.
+pub fn random_equipment(ecs: &mut World, x: i32, y: i32) {
+    use bracket_lib::color::*;
+    let to_cp437 = bracket_lib::prelude::to_cp437;
+
+    match roll(ecs, 1, 10) {
+        1 => {
+            let dagger = equipment(ecs, x, y, to_cp437('/'), LIGHT_GRAY, "Dagger", EquipmentSlot::Melee);
+            add_damage(ecs, dagger, MeleeDamage{ n_dice: 1, die_type: 4, bonus: 0, kind: DamageType::Piercing });
+            add_power(ecs, dagger, 2);
+        }
+        2 => {
+            let sword = equipment(ecs, x, y, to_cp437('/'), CYAN, "Longsword", EquipmentSlot::Melee);
+            add_damage(ecs, sword, MeleeDamage{ n_dice: 1, die_type: 8, bonus: 1, kind: DamageType::Slashing });
+        }
+        3 => {
+            let mace = equipment(ecs, x, y, to_cp437('/'), GRAY, "Mace", EquipmentSlot::Melee);
+            add_damage(ecs, mace, MeleeDamage{ n_dice: 2, die_type: 4, bonus: 0, kind: DamageType::Bludgeoning });
+            add_power(ecs, mace, 1);
+        }
+        4 => {
+            let buckler = equipment(ecs, x, y, to_cp437('('), BURLYWOOD, "Buckler", EquipmentSlot::Shield);
+            add_defense(ecs, buckler, 1);
+        }
+        5 => {
+            // Hard to get past, and hard to swing around
+            let shield = equipment(ecs, x, y, to_cp437('('), CYAN, "Tower Shield", EquipmentSlot::Shield);
+            add_defense(ecs, shield, 3);
+            add_power(ecs, shield, -1);
+        }
+        6 => {
+            let cap = equipment(ecs, x, y, to_cp437('['), BURLYWOOD, "Leather Cap", EquipmentSlot::Head);
+            add_defense(ecs, cap, 1);
+        }
+        7 => {
+            let mail = equipment(ecs, x, y, to_cp437('['), CYAN, "Chain Mail", EquipmentSlot::Body);
+            add_defense(ecs, mail, 2);
+        }
+        8 => {
+            let boots = equipment(ecs, x, y, to_cp437('['), BROWN1, "Leather Boots", EquipmentSlot::Feet);
+            add_defense(ecs, boots, 1);
+        }
+        9 => {
+            let ring = equipment(ecs, x, y, to_cp437('='), GOLD, "Ring of Might", EquipmentSlot::Ring);
+            add_power(ecs, ring, 2);
+        }
+        _ => {
+            let ring = equipment(ecs, x, y, to_cp437('='), SILVER, "Ring of Protection", EquipmentSlot::Ring);
+            add_defense(ecs, ring, 2);
+        }
+    }
+}
+
+/// Equipment lying at (x, y), with no bonuses until they're added.
+fn equipment<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: bracket_lib::prelude::FontCharType, foreground: (u8, u8, u8),
+    name: S, slot: EquipmentSlot) -> Entity {
+    ecs.create_entity()
+        .with(Position{x, y})
+        .with(Renderable {
+            glyph,
+            foreground: RGB::named(foreground),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 2
+        })
+        .with(Name{name: name.to_string()})
+        .with(Item{})
+        .with(Equippable{ slot })
+        .build()
+}
+
+/// Wielding this replaces the wielder's own damage.
+fn add_damage(ecs: &mut World, item: Entity, damage: MeleeDamage) {
+    ecs.write_storage::<MeleeDamage>().insert(item, damage).expect("Unable to insert damage");
+}
+
+fn add_power(ecs: &mut World, item: Entity, power: i32) {
+    ecs.write_storage::<MeleePowerBonus>().insert(item, MeleePowerBonus{ power }).expect("Unable to insert power bonus");
+}
+
+fn add_defense(ecs: &mut World, item: Entity, defense: i32) {
+    ecs.write_storage::<DefenseBonus>().insert(item, DefenseBonus{ defense }).expect("Unable to insert defense bonus");
+}
+
+/// Something to read.
+pub fn random_scroll(ecs: &mut World, x: i32, y: i32) {
+    match roll(ecs, 1, 4) {
+        1 => fireball_scroll(ecs, x, y),
+        2 => confusion_scroll(ecs, x, y),
+        3 => identify_scroll(ecs, x, y),
+        _ => magic_missile_scroll(ecs, x, y)
+    }
+}
+
+/// Something nobody would drink on purpose, once they knew what it was.
+fn random_bad_potion(ecs: &mut World, x: i32, y: i32) {
+    match roll(ecs, 1, 6) {
+        1 => status_potion(ecs, x, y, "Potion of Poison", StatusKind::Poisoned, 10),
+        2 => status_potion(ecs, x, y, "Potion of Confusion", StatusKind::Confused, 8),
+        3 => status_potion(ecs, x, y, "Potion of Sleeping", StatusKind::Asleep, 6),
+        4 => status_potion(ecs, x, y, "Potion of Slowness", StatusKind::Slowed, 12),
+        5 => volatile_potion(ecs, x, y),
+        _ => status_potion(ecs, x, y, "Potion of Blindness", StatusKind::Blinded, 10)
     }
 }
 
@@ -80,23 +306,253 @@ pub fn random_monster(ecs: &mut World, x: i32, y: i32) {
     let roll :i32;
     {
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1,2);
+        roll = rng.roll_dice(1,20);
     }
     match roll {
-        1 => {orc(ecs, x, y)}
+        1..=4 => {orc(ecs, x, y)}
+        5..=7 => {goblin_archer(ecs, x, y)}
+        8 => {necromancer(ecs, x, y)}
+        9 => {ogre(ecs, x, y)}
+        10 => {giant_rat(ecs, x, y)}
+        11 => {goblin_shaman(ecs, x, y)}
+        12 => {orc_warchief(ecs, x, y)}
+        13 => {goblin_cutpurse(ecs, x, y)}
+        14 => {fire_drake(ecs, x, y)}
+        15 => {ooze(ecs, x, y)}
         _ => {goblin(ecs, x, y)}
     }
 }
 
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, bracket_lib::prelude::to_cp437('o'), "Orc");
+pub fn orc(ecs: &mut World, x: i32, y: i32) {
+    let orc = monster(ecs, x, y, bracket_lib::prelude::to_cp437('o'), "Orc", Faction::Orcs, MeleeDamage{ n_dice: 1, die_type: 6, bonus: 0, kind: DamageType::Slashing },
+        Resistances{ resistant: vec![DamageType::Poison], vulnerable: Vec::new() });
+    ecs.write_storage::<LootTable>().insert(orc, LootTable{ drops: vec![(LootItem::HealthPotion, 30), (LootItem::AnyItem, 10)] })
+        .expect("Unable to insert loot table");
+}
+
+pub fn goblin(ecs: &mut World, x: i32, y: i32) {
+    let goblin = monster(ecs, x, y, bracket_lib::prelude::to_cp437('g'), "Goblin", Faction::Goblins, MeleeDamage{ n_dice: 1, die_type: 4, bonus: 0, kind: DamageType::Piercing },
+        Resistances{ resistant: Vec::new(), vulnerable: vec![DamageType::Fire] });
+    ecs.write_storage::<LootTable>().insert(goblin, LootTable{ drops: vec![(LootItem::AnyItem, 20)] })
+        .expect("Unable to insert loot table");
+}
+
+/// A goblin that hangs back and shoots, until it runs out of arrows.
+pub fn goblin_archer(ecs: &mut World, x: i32, y: i32) {
+    let archer = monster(ecs, x, y, bracket_lib::prelude::to_cp437('g'), "Goblin Archer", Faction::Goblins, MeleeDamage{ n_dice: 1, die_type: 3, bonus: 0, kind: DamageType::Piercing },
+        Resistances{ resistant: Vec::new(), vulnerable: vec![DamageType::Fire] });
+
+    let bow = shortbow(ecs);
+    ecs.write_storage::<InBackpack>().insert(bow, InBackpack{ owner: archer }).expect("Unable to insert backpack entry");
+    let count = 2 + roll(ecs, 1, 6);
+    let arrows = arrows(ecs, count);
+    ecs.write_storage::<InBackpack>().insert(arrows, InBackpack{ owner: archer }).expect("Unable to insert backpack entry");
+    ecs.write_storage::<LootTable>().insert(archer, LootTable{ drops: vec![(LootItem::Arrows, 50)] })
+        .expect("Unable to insert loot table");
+}
+
+/// Minds its own business unless something picks a fight with it.
+pub fn giant_rat(ecs: &mut World, x: i32, y: i32) {
+    let rat = monster(ecs, x, y, bracket_lib::prelude::to_cp437('r'), "Giant Rat", Faction::Wildlife, MeleeDamage{ n_dice: 1, die_type: 3, bonus: 0, kind: DamageType::Piercing },
+        Resistances{ resistant: Vec::new(), vulnerable: Vec::new() });
+    set_stats(ecs, rat, 6, 0, 2);
+    set_foreground(ecs, rat, RGB::named(bracket_lib::color::SADDLEBROWN));
+}
+
+/// Heals its friends and throws fire at its enemies.
+pub fn goblin_shaman(ecs: &mut World, x: i32, y: i32) {
+    let shaman = monster(ecs, x, y, bracket_lib::prelude::to_cp437('g'), "Goblin Shaman", Faction::Goblins, MeleeDamage{ n_dice: 1, die_type: 3, bonus: 0, kind: DamageType::Bludgeoning },
+        Resistances{ resistant: Vec::new(), vulnerable: vec![DamageType::Fire] });
+    set_stats(ecs, shaman, 12, 0, 3);
+    set_foreground(ecs, shaman, RGB::named(bracket_lib::color::MAGENTA));
+    give_abilities(ecs, shaman, vec![
+        Ability::new(AbilityKind::HealAllies{ range: 6.0, amount: 8 }, 10),
+        Ability::new(AbilityKind::Spell{ range: 6.0, n_dice: 1, die_type: 6, kind: DamageType::Fire, inflicts: None }, 4)
+    ]);
+    ecs.write_storage::<LootTable>().insert(shaman, LootTable{ drops: vec![(LootItem::HealthPotion, 40), (LootItem::AnyItem, 20)] })
+        .expect("Unable to insert loot table");
+}
+
+/// Grabs something from your pack while you're busy fighting it.
+pub fn goblin_cutpurse(ecs: &mut World, x: i32, y: i32) {
+    let cutpurse = monster(ecs, x, y, bracket_lib::prelude::to_cp437('g'), "Goblin Cutpurse", Faction::Goblins, MeleeDamage{ n_dice: 1, die_type: 4, bonus: 0, kind: DamageType::Piercing },
+        Resistances{ resistant: Vec::new(), vulnerable: vec![DamageType::Fire] });
+    set_foreground(ecs, cutpurse, RGB::named(bracket_lib::color::GOLD));
+    give_abilities(ecs, cutpurse, vec![Ability::new(AbilityKind::Steal, 20)]);
+    ecs.write_storage::<LootTable>().insert(cutpurse, LootTable{ drops: vec![(LootItem::AnyItem, 30)] })
+        .expect("Unable to insert loot table");
+}
+
+/// Blows a horn for more orcs once it's in a fight.
+pub fn orc_warchief(ecs: &mut World, x: i32, y: i32) {
+    let warchief = monster(ecs, x, y, bracket_lib::prelude::to_cp437('o'), "Orc Warchief", Faction::Orcs, MeleeDamage{ n_dice: 1, die_type: 8, bonus: 0, kind: DamageType::Slashing },
+        Resistances{ resistant: vec![DamageType::Poison], vulnerable: Vec::new() });
+    set_stats(ecs, warchief, 18, 2, 5);
+    set_foreground(ecs, warchief, RGB::named(bracket_lib::color::ORANGE_RED));
+    give_abilities(ecs, warchief, vec![Ability::new(AbilityKind::Summon{ count: 1, spawn: orc }, 40)]);
+    ecs.write_storage::<LootTable>().insert(warchief, LootTable{ drops: vec![(LootItem::HealthPotion, 50), (LootItem::AnyItem, 50)] })
+        .expect("Unable to insert loot table");
 }
 
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, bracket_lib::prelude::to_cp437('g'), "Goblin");
+/// Breathes a cone of fire every few turns.
+pub fn fire_drake(ecs: &mut World, x: i32, y: i32) {
+    let drake = monster(ecs, x, y, bracket_lib::prelude::to_cp437('d'), "Fire Drake", Faction::Beasts, MeleeDamage{ n_dice: 1, die_type: 6, bonus: 0, kind: DamageType::Piercing },
+        Resistances{ resistant: vec![DamageType::Fire], vulnerable: vec![DamageType::Cold] });
+    set_stats(ecs, drake, 18, 1, 4);
+    set_foreground(ecs, drake, RGB::named(bracket_lib::color::ORANGE));
+    give_abilities(ecs, drake, vec![
+        Ability::new(AbilityKind::Breath{ radius: 4, n_dice: 2, die_type: 6, kind: DamageType::Fire }, 8)
+    ]);
 }
 
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: bracket_lib::prelude::FontCharType, name: S) {
+/// Splits in two when hit, so it's best killed in as few blows as possible.
+pub fn ooze(ecs: &mut World, x: i32, y: i32) {
+    let ooze = monster(ecs, x, y, bracket_lib::prelude::to_cp437('j'), "Ooze", Faction::Beasts, MeleeDamage{ n_dice: 1, die_type: 4, bonus: 0, kind: DamageType::Bludgeoning },
+        Resistances{ resistant: vec![DamageType::Slashing, DamageType::Piercing], vulnerable: Vec::new() });
+    set_stats(ecs, ooze, 20, 0, 3);
+    set_foreground(ecs, ooze, RGB::named(bracket_lib::color::GREEN));
+    give_abilities(ecs, ooze, vec![Ability::new(AbilityKind::Split, 6)]);
+}
+
+/// Splits `parent` in two, moving half its hit points into a copy of it on a free tile alongside.
+/// The copy has every ability of the parent except splitting. Nothing happens if there's no room
+/// or too little of it left to share. Returns whether it split.
+pub fn split_off(ecs: &mut World, parent: Entity) -> bool {
+    let spot = {
+        let positions = ecs.read_storage::<Position>();
+        let map = ecs.fetch::<Map>();
+        let spot = positions.get(parent).and_then(|pos| map.free_tiles_around(Point::new(pos.x, pos.y)).first().copied());
+        match spot {
+            Some(spot) => spot,
+            None => return false
+        }
+    };
+    let stats = {
+        let mut combat_stats = ecs.write_storage::<CombatStats>();
+        let stats = match combat_stats.get_mut(parent) {
+            Some(stats) if stats.hp > 1 => stats,
+            _ => return false
+        };
+        let half = stats.hp / 2;
+        stats.hp -= half;
+        CombatStats{ max_hp: stats.max_hp, hp: half, defense: stats.defense, power: stats.power }
+    };
+
+    let renderable = ecs.read_storage::<Renderable>().get(parent).cloned();
+    let name = ecs.read_storage::<Name>().get(parent).cloned();
+    let damage = ecs.read_storage::<MeleeDamage>().get(parent).copied();
+    let resistances = ecs.read_storage::<Resistances>().get(parent).cloned();
+    let perception = ecs.read_storage::<Perception>().get(parent).map(|p| Perception{ value: p.value });
+    let awareness = ecs.read_storage::<Awareness>().get(parent).map(|a| Awareness{ state: a.state });
+    let faction = ecs.read_storage::<Faction>().get(parent).copied();
+    // The copy can't split again, or a few hits would fill the room
+    let abilities = ecs.read_storage::<Abilities>().get(parent).map(|a| Abilities{
+        abilities: a.abilities.iter().filter(|ability| !matches!(ability.kind, AbilityKind::Split)).cloned().collect()
+    });
+    let range = ecs.read_storage::<Viewshed>().get(parent).map_or(8, |v| v.range);
+
+    ecs.create_entity()
+        .with(Position{ x: spot.x, y: spot.y })
+        .with(Viewshed{ visible_tiles: Vec::new(), range, dirty: true })
+        .with(Monster{})
+        .with(BlocksTile{})
+        .with(stats)
+        .maybe_with(renderable)
+        .maybe_with(name)
+        .maybe_with(damage)
+        .maybe_with(resistances)
+        .maybe_with(perception)
+        .maybe_with(awareness)
+        .maybe_with(faction)
+        .maybe_with(abilities)
+        .build();
+    true
+}
+
+/// Big, slow to kill, and swings a club hard enough to send you flying.
+pub fn ogre(ecs: &mut World, x: i32, y: i32) {
+    let ogre = monster(ecs, x, y, bracket_lib::prelude::to_cp437('O'), "Ogre", Faction::Orcs, MeleeDamage{ n_dice: 1, die_type: 8, bonus: 1, kind: DamageType::Bludgeoning },
+        Resistances{ resistant: Vec::new(), vulnerable: Vec::new() });
+    set_stats(ecs, ogre, 26, 1, 4);
+    ecs.write_storage::<KnocksBack>().insert(ogre, KnocksBack{ distance: 2 }).expect("Unable to insert knockback");
+    ecs.write_storage::<LootTable>().insert(ogre, LootTable{ drops: vec![(LootItem::HealthPotion, 50), (LootItem::AnyItem, 30)] })
+        .expect("Unable to insert loot table");
+}
+
+/// Weak in a fight, but raises any corpse it sees to fight for it.
+pub fn necromancer(ecs: &mut World, x: i32, y: i32) {
+    let necromancer = monster(ecs, x, y, bracket_lib::prelude::to_cp437('N'), "Necromancer", Faction::Undead, MeleeDamage{ n_dice: 1, die_type: 4, bonus: 0, kind: DamageType::Cold },
+        Resistances{ resistant: vec![DamageType::Cold, DamageType::Poison], vulnerable: Vec::new() });
+    give_abilities(ecs, necromancer, vec![Ability::new(AbilityKind::RaiseDead{ range: 6.0 }, 15)]);
+    ecs.write_storage::<LootTable>().insert(necromancer, LootTable{ drops: vec![(LootItem::AnyItem, 60)] })
+        .expect("Unable to insert loot table");
+}
+
+/// A corpse raised by a necromancer. It knows exactly where you are.
+pub fn zombie(ecs: &mut World, x: i32, y: i32, of: &str) {
+    let player = *ecs.fetch::<Entity>();
+    let player_pos = *ecs.fetch::<Point>();
+    ecs.create_entity()
+        .with(Position {x, y})
+        .with(Renderable {
+            glyph: bracket_lib::prelude::to_cp437('z'),
+            foreground: RGB::named(bracket_lib::color::OLIVE),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 1
+        })
+        .with(Viewshed{visible_tiles: Vec::new(), range: 6, dirty: true})
+        .with(Monster{})
+        .with(Name {name: format!("{} Zombie", of)})
+        .with(BlocksTile{})
+        .with(CombatStats{max_hp: 10, hp: 10, defense: 0, power: 3})
+        .with(MeleeDamage{ n_dice: 1, die_type: 4, bonus: 0, kind: DamageType::Slashing })
+        .with(Resistances{ resistant: vec![DamageType::Poison, DamageType::Cold], vulnerable: vec![DamageType::Fire] })
+        .with(Perception{ value: 0 })
+        .with(Awareness{ state: AwarenessState::Hunting{ target: player, last_seen: player_pos } })
+        .with(Faction::Undead)
+        .with(Undead{})
+        .build();
+}
+
+/// What's left of a monster. Drawn under anything lying on top of it.
+pub fn corpse(ecs: &mut World, x: i32, y: i32, of: &str, foreground: Option<RGB>, rots: i32) {
+    ecs.create_entity()
+        .with(Position{x, y})
+        .with(Renderable {
+            glyph: bracket_lib::prelude::to_cp437('%'),
+            foreground: foreground.unwrap_or(RGB::named(bracket_lib::color::RED)),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 3
+        })
+        .with(Name{name: format!("{} corpse", of.to_lowercase())})
+        .with(Corpse{ monster: of.to_string(), rots })
+        .build();
+}
+
+/// Overrides the hit points, defense and power every monster starts with.
+fn set_stats(ecs: &mut World, monster: Entity, hp: i32, defense: i32, power: i32) {
+    let mut stats = ecs.write_storage::<CombatStats>();
+    if let Some(stats) = stats.get_mut(monster) {
+        *stats = CombatStats{ max_hp: hp, hp, defense, power };
+    }
+}
+
+fn set_foreground(ecs: &mut World, monster: Entity, foreground: RGB) {
+    if let Some(render) = ecs.write_storage::<Renderable>().get_mut(monster) {
+        render.foreground = foreground;
+    }
+}
+
+fn give_abilities(ecs: &mut World, monster: Entity, abilities: Vec<Ability>) {
+    ecs.write_storage::<Abilities>().insert(monster, Abilities{ abilities }).expect("Unable to insert abilities");
+}
+
+#[allow(clippy::too_many_arguments)]
+fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: bracket_lib::prelude::FontCharType, name: S, faction: Faction,
+    damage: MeleeDamage, resistances: Resistances) -> Entity {
+    // A third of monsters are found asleep; the rest are awake but haven't noticed the player
+    let state = if roll(ecs, 1, 3) == 1 { AwarenessState::Asleep } else { AwarenessState::Unaware };
+
     ecs.create_entity()
         .with(Position {x, y})
         .with(Renderable {
@@ -110,21 +566,213 @@ fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: bracket_lib::pre
         .with(Name {name: name.to_string()})
         .with(BlocksTile{})
         .with(CombatStats{max_hp: 16, hp: 16, defense: 1, power: 4})
-        .build();
-        
+        .with(damage)
+        .with(resistances)
+        .with(Perception{ value: 0 })
+        .with(Awareness{ state })
+        .with(faction)
+        .build()
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
+/// A potion lying at (x, y), used up once drunk. It looks like every other potion with the same
+/// name, and goes by that look until the player identifies it. Add what it does before building it.
+fn potion<'a>(ecs: &'a mut World, x: i32, y: i32, name: &str) -> EntityBuilder<'a> {
+    let (look, identified) = {
+        let mut appearances = ecs.write_resource::<ItemAppearances>();
+        (appearances.potion(name), appearances.is_identified(name))
+    };
     ecs.create_entity()
         .with(Position{x,y})
         .with(Renderable {
             glyph: bracket_lib::prelude::to_cp437('i'),
-            foreground: RGB::named(bracket_lib::color::YELLOW),
+            foreground: RGB::named(look.colour),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 2
+        })
+        .with(Name{name: name.to_string()})
+        .maybe_with(if identified { None } else { Some(Unidentified{ name: look.name }) })
+        .with(Item{})
+        .with(Potion{})
+        .with(Consumable{})
+}
+
+fn health_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Potion of Healing")
+        .with(ProvidesHealing { heal_amount: 8 })
+        .build();
+}
+
+fn telepathy_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Potion of Telepathy")
+        .with(GrantsTelepathy { range: 20, duration: 40 })
+        .build();
+}
+
+fn detection_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Potion of Object Detection")
+        .with(GrantsItemDetection { range: 30, duration: 20 })
+        .build();
+}
+
+fn magic_mapping_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Potion of Clairvoyance")
+        .with(GrantsMagicMapping {})
+        .build();
+}
+
+fn amnesia_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Potion of Forgetfulness")
+        .with(CausesAmnesia { radius: 10 })
+        .build();
+}
+
+fn teleport_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Potion of Teleportation")
+        .with(Teleports {})
+        .build();
+}
+
+/// Bursts into flame around whoever drinks it.
+fn volatile_potion(ecs: &mut World, x: i32, y: i32) {
+    potion(ecs, x, y, "Volatile Potion")
+        .with(InflictsDamage { n_dice: 2, die_type: 6, kind: DamageType::Fire })
+        .with(AreaOfEffect { radius: 2 })
+        .with(KnocksBack { distance: 2 })
+        .build();
+}
+
+fn status_potion(ecs: &mut World, x: i32, y: i32, name: &str, kind: StatusKind, turns: i32) {
+    potion(ecs, x, y, name)
+        .with(GrantsStatus { kind, turns })
+        .build();
+}
+
+/// A scroll lying at (x, y), used up once read. It carries the same label as every other scroll
+/// with the same name, and goes by that label until the player identifies it. Add what it does
+/// before building it.
+fn scroll<'a>(ecs: &'a mut World, x: i32, y: i32, name: &str) -> EntityBuilder<'a> {
+    let (label, identified) = {
+        let mut appearances = ecs.write_resource::<ItemAppearances>();
+        (appearances.scroll(name), appearances.is_identified(name))
+    };
+    ecs.create_entity()
+        .with(Position{x,y})
+        .with(Renderable {
+            glyph: bracket_lib::prelude::to_cp437('?'),
+            foreground: RGB::named(bracket_lib::color::WHEAT),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 2
+        })
+        .with(Name{name: name.to_string()})
+        .maybe_with(if identified { None } else { Some(Unidentified{ name: label }) })
+        .with(Item{})
+        .with(Scroll{})
+        .with(Consumable{})
+}
+
+fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
+    scroll(ecs, x, y, "Scroll of Magic Missile")
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage { n_dice: 2, die_type: 6, kind: DamageType::Magic })
+        .build();
+}
+
+fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
+    scroll(ecs, x, y, "Scroll of Fireball")
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage { n_dice: 3, die_type: 6, kind: DamageType::Fire })
+        .with(AreaOfEffect { radius: 2 })
+        .with(KnocksBack { distance: 1 })
+        .build();
+}
+
+fn identify_scroll(ecs: &mut World, x: i32, y: i32) {
+    scroll(ecs, x, y, "Scroll of Identify")
+        .with(Identifies {})
+        .build();
+}
+
+fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
+    scroll(ecs, x, y, "Scroll of Confusion")
+        .with(Ranged { range: 6 })
+        .with(GrantsStatus { kind: StatusKind::Confused, turns: 6 })
+        .build();
+}
+
+/// Created without a position; put it on the map or in a backpack.
+fn shortbow(ecs: &mut World) -> Entity {
+    ecs.create_entity()
+        .with(Renderable {
+            glyph: bracket_lib::prelude::to_cp437('}'),
+            foreground: RGB::named(bracket_lib::color::BURLYWOOD),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 2
+        })
+        .with(Name{name: "Shortbow".to_string()})
+        .with(Item{})
+        .with(RangedWeapon{ range: 8, n_dice: 1, die_type: 6, bonus: 0, kind: DamageType::Piercing })
+        .build()
+}
+
+/// Created without a position; put it on the map or in a backpack.
+fn arrows(ecs: &mut World, count: i32) -> Entity {
+    ecs.create_entity()
+        .with(Renderable {
+            glyph: bracket_lib::prelude::to_cp437('/'),
+            foreground: RGB::named(bracket_lib::color::BURLYWOOD),
             background: RGB::named(bracket_lib::color::BLACK),
             render_order: 2
         })
-        .with(Name{name: "Potion".to_string()})
+        .with(Name{name: "Arrows".to_string()})
         .with(Item{})
-        .with(Potion { heal_amount: 8 })
+        .with(Ammunition{ count })
+        .build()
+}
+
+/// Scenery: visible and named for tooltips, but it doesn't block and can't be picked up.
+fn decoration<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: bracket_lib::prelude::FontCharType, name: S) {
+    ecs.create_entity()
+        .with(Position{x, y})
+        .with(Renderable {
+            glyph,
+            foreground: RGB::named(bracket_lib::color::BURLYWOOD),
+            background: RGB::named(bracket_lib::color::BLACK),
+            render_order: 3
+        })
+        .with(Name{name: name.to_string()})
         .build();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{register_components, TileType};
+
+    #[test]
+    fn split_copies_cannot_split_again() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        let mut map = Map::new(10, 10);
+        for y in 1..9 {
+            for x in 1..9 {
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+        map.populate_blocked();
+        ecs.insert(map);
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        ooze(&mut ecs, 5, 5);
+        let parent = (&ecs.entities(), &ecs.read_storage::<Monster>()).join().map(|(entity, _monster)| entity).next().unwrap();
+
+        assert!(split_off(&mut ecs, parent));
+        ecs.maintain();
+
+        let abilities = ecs.read_storage::<Abilities>();
+        let stats = ecs.read_storage::<CombatStats>();
+        let copy = (&ecs.entities(), &ecs.read_storage::<Monster>()).join().map(|(entity, _monster)| entity).find(|entity| *entity != parent).unwrap();
+        assert!(abilities.get(parent).unwrap().abilities.iter().any(|ability| matches!(ability.kind, AbilityKind::Split)));
+        assert!(!abilities.get(copy).unwrap().abilities.iter().any(|ability| matches!(ability.kind, AbilityKind::Split)));
+        assert_eq!(stats.get(parent).unwrap().hp + stats.get(copy).unwrap().hp, 20);
+    }
+}
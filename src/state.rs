@@ -1,35 +1,54 @@
-use bracket_lib::prelude::{BTerm, GameState};
+use bracket_lib::prelude::{BTerm, GameState, Point, RGB};
 use specs::prelude::*;
 
-use crate::melee_combat_system::MeleeCombatSystem;
+use crate::{MeleeCombatSystem, RangedCombatSystem, AbilitySystem, AreaAttackSystem, KnockbackSystem};
 
 use super::MapIndexingSystem;
 use super::damage_system;
 use super::draw_map;
 use super::{
     VisibilitySystem,
+    SensingSystem,
     Position,
     Renderable,
     DamageSystem,
     Map,
     MonsterAI,
     ItemCollectionSystem,
-    ItemDropSystem, gui, Name, gamelog, PotionUseSystem, WantsToDropItem, WantsToDrinkPotion
+    ItemDropSystem, gui, ItemUseSystem, WantsToDropItem, WantsToUseItem, WantsToShoot,
+    MemorySystem, EntityMemory, NoiseSystem, StatusEffectSystem, StatusEffects, StatusKind,
+    CorpseDecaySystem, RaiseDeadSystem, RegenerationSystem,
+    ItemEquipSystem, ItemUnequipSystem, Equippable, Equipped, WantsToEquip, WantsToUnequip, Ranged
 };
 
 use super::player_input;
 use super::draw_ui;
 
 #[derive(PartialEq, Copy, Clone)]
-pub enum RunState { AwaitingInput, PreRun, PlayerTurn, MonsterTurn, ShowInventory, ShowDropItem }
+pub enum RunState { AwaitingInput, PreRun, PlayerTurn, MonsterTurn, ShowInventory, ShowDropItem,
+    /// Magic mapping sweeps down the level, revealing one row per frame.
+    MagicMapReveal { row: i32 },
+    /// Choosing what to shoot, with `target` currently highlighted.
+    ShowTargeting { target: Option<Entity> },
+    /// Choosing where to aim `item`, with the tile `target` currently highlighted.
+    ShowItemTargeting { item: Entity, target: Option<Point> },
+    ShowCharacter
+}
+
 
+/// Counts completed game turns, i.e. each time the monsters have had their go.
+pub struct GameTurn {
+    pub number: i32
+}
 
 pub struct State {
     pub ecs:World,
 }
 
 impl State {
-    fn run_systems(&mut self) {
+    /// Runs every system once, in game order. Public so headless tools, like the balance
+    /// simulator, play out turns exactly as the game does.
+    pub fn run_systems(&mut self) {
         let mut vis = VisibilitySystem{};
         vis.run_now(&self.ecs);
         let mut mob = MonsterAI{};
@@ -38,16 +57,47 @@ impl State {
         mapindex.run_now(&self.ecs);
         let mut melee = MeleeCombatSystem{};
         melee.run_now(&self.ecs);
+        let mut ranged = RangedCombatSystem{};
+        ranged.run_now(&self.ecs);
+        let mut abilities = AbilitySystem{};
+        abilities.run_now(&self.ecs);
+        let mut items = ItemUseSystem{};
+        items.run_now(&self.ecs);
+        let mut area = AreaAttackSystem{};
+        area.run_now(&self.ecs);
+        let mut knockback = KnockbackSystem{};
+        knockback.run_now(&self.ecs);
+        let mut noise = NoiseSystem{};
+        noise.run_now(&self.ecs);
+        let mut status = StatusEffectSystem{};
+        status.run_now(&self.ecs);
+        let mut regeneration = RegenerationSystem{};
+        regeneration.run_now(&self.ecs);
+        let mut raise_dead = RaiseDeadSystem{};
+        raise_dead.run_now(&self.ecs);
+        let mut decay = CorpseDecaySystem{};
+        decay.run_now(&self.ecs);
         let mut damage = DamageSystem{};
         damage.run_now(&self.ecs);
         let mut pickup = ItemCollectionSystem{};
         pickup.run_now(&self.ecs);
-        let mut potions = PotionUseSystem{};
-        potions.run_now(&self.ecs);
         let mut drop_items = ItemDropSystem{};
         drop_items.run_now(&self.ecs);
+        let mut equip = ItemEquipSystem{};
+        equip.run_now(&self.ecs);
+        let mut unequip = ItemUnequipSystem{};
+        unequip.run_now(&self.ecs);
+        let mut sensing = SensingSystem{};
+        sensing.run_now(&self.ecs);
+        let mut memory = MemorySystem{};
+        memory.run_now(&self.ecs);
         self.ecs.maintain();
     }
+
+    fn player_has(&self, kind: StatusKind) -> bool {
+        let statuses = self.ecs.read_storage::<StatusEffects>();
+        statuses.get(*self.ecs.fetch::<Entity>()).is_some_and(|s| s.has(kind))
+    }
 }
 
 impl GameState for State {
@@ -56,18 +106,40 @@ impl GameState for State {
 
         draw_map(&self.ecs, ctx);
 
+        // Light up the leading edge of a magic mapping sweep
+        if let RunState::MagicMapReveal{ row } = *self.ecs.fetch::<RunState>() {
+            for x in 0..self.ecs.fetch::<Map>().width {
+                ctx.set_bg(x, row, RGB::from_f32(0.2, 0.2, 0.6));
+            }
+        }
+
         {
             let positions = self.ecs.read_storage::<Position>();
             let renderables = self.ecs.read_storage::<Renderable>();
             let map = self.ecs.fetch::<Map>();
+            let memory = self.ecs.fetch::<EntityMemory>();
 
-            let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-            data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
+            // Things the player saw earlier but can't see now are drawn greyed out, like
+            // revealed tiles
+            let mut remembered = memory.entities.iter()
+                .filter(|(entity, m)| !map.visible_tiles[map.xy_idx(m.x, m.y)] && !map.sensed.contains(entity))
+                .map(|(_entity, m)| m)
+                .collect::<Vec<_>>();
+            remembered.sort_by_key(|m| std::cmp::Reverse(m.render_order));
+            for m in remembered.iter() {
+                ctx.set(m.x, m.y, m.foreground.to_greyscale(), RGB::from_f32(0., 0., 0.), m.glyph);
+            }
+
+            let mut data = (&self.ecs.entities(), &positions, &renderables).join().collect::<Vec<_>>();
+            data.sort_by_key(|&a| std::cmp::Reverse(a.2.render_order));
 
-            for (pos, render) in data.iter() {
+            for (entity, pos, render) in data.iter() {
                 let idx = map.xy_idx(pos.x, pos.y);
                 if map.visible_tiles[idx] {
                     ctx.set(pos.x, pos.y, render.foreground, render.background, render.glyph);
+                } else if map.sensed.contains(entity) {
+                    // Sensed but not seen: shown on a purple haze so it's never mistaken for sight
+                    ctx.set(pos.x, pos.y, render.foreground, RGB::from_f32(0.3, 0.0, 0.4), render.glyph);
                 }
             }
 
@@ -92,12 +164,30 @@ impl GameState for State {
             RunState::PlayerTurn => {
                 self.run_systems();
                 self.ecs.maintain();
-                newrunstate = RunState::MonsterTurn;
+                newrunstate = match *self.ecs.fetch::<RunState>() {
+                    RunState::MagicMapReveal{ .. } => RunState::MagicMapReveal{ row: 0 },
+                    _ => RunState::MonsterTurn
+                };
             }
             RunState::MonsterTurn => {
                 self.run_systems();
                 self.ecs.maintain();
-                newrunstate = RunState::AwaitingInput;
+                self.ecs.write_resource::<GameTurn>().number += 1;
+                // A slowed player gives the monsters two goes for each of theirs
+                if self.player_has(StatusKind::Slowed) && self.ecs.fetch::<GameTurn>().number % 2 == 1 {
+                    newrunstate = RunState::MonsterTurn;
+                } else {
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::MagicMapReveal{ row } => {
+                let mut map = self.ecs.fetch_mut::<Map>();
+                map.reveal_row(row);
+                if row >= map.height - 1 {
+                    newrunstate = RunState::MonsterTurn;
+                } else {
+                    newrunstate = RunState::MagicMapReveal{ row: row + 1 };
+                }
             }
             RunState::ShowInventory => {
                 let result = gui::show_inventory(self, ctx);
@@ -106,13 +196,55 @@ impl GameState for State {
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
                         let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDrinkPotion>();
-                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToDrinkPotion{potion: item_entity}).expect("Unable to insert intent");
-
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        // Equipment goes on or comes off, aimed items need a target, anything else gets used
                         newrunstate = RunState::PlayerTurn;
+                        if self.ecs.read_storage::<Equipped>().contains(item_entity) {
+                            let mut intent = self.ecs.write_storage::<WantsToUnequip>();
+                            intent.insert(player_entity, WantsToUnequip{ item: item_entity }).expect("Unable to insert intent");
+                        } else if self.ecs.read_storage::<Equippable>().contains(item_entity) {
+                            let mut intent = self.ecs.write_storage::<WantsToEquip>();
+                            intent.insert(player_entity, WantsToEquip{ item: item_entity }).expect("Unable to insert intent");
+                        } else if self.ecs.read_storage::<Ranged>().contains(item_entity) {
+                            let target = gui::item_targets(&self.ecs, item_entity).first().copied();
+                            newrunstate = RunState::ShowItemTargeting{ item: item_entity, target };
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                            intent.insert(player_entity, WantsToUseItem{item: item_entity, target: None}).expect("Unable to insert intent");
+                        }
                     }
                 }
             },
+            RunState::ShowTargeting{ target } => {
+                let result = gui::ranged_target(self, ctx, target);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => newrunstate = RunState::ShowTargeting{ target: result.1 },
+                    gui::ItemMenuResult::Selected => {
+                        let target = result.1.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToShoot>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToShoot{ target }).expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowItemTargeting{ item, target } => {
+                let result = gui::item_target(self, ctx, item, target);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => newrunstate = RunState::ShowItemTargeting{ item, target: result.1 },
+                    gui::ItemMenuResult::Selected => {
+                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem{ item, target: result.1 }).expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowCharacter => {
+                if gui::show_character(self, ctx) == gui::ItemMenuResult::Cancel {
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
             RunState::ShowDropItem => {
                 let result = gui::drop_item_menu(self, ctx);
                 match result.0 {
@@ -0,0 +1,66 @@
+use specs::prelude::*;
+use super::{StatusEffects, StatusKind, CombatStats, SufferDamage, DamageType, Viewshed, RunState, Name, gamelog::GameLog};
+
+/// Poison damage taken each turn.
+const POISON_DAMAGE: i32 = 1;
+/// Hit points regained each turn while regenerating.
+const REGENERATION_HEAL: i32 = 1;
+
+/// Applies every status effect once per game turn and counts down their durations.
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, StatusEffects>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, Viewshed>,
+                        ReadStorage<'a, Name>
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, runstate, mut log, entities, mut statuses, mut combat_stats, mut inflict_damage,
+            mut viewsheds, names) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        for (entity, status, stats) in (&entities, &mut statuses, &mut combat_stats).join() {
+            let is_player = entity == *player_entity;
+
+            for effect in status.effects.iter_mut() {
+                match effect.kind {
+                    StatusKind::Poisoned => {
+                        SufferDamage::new_damage(&mut inflict_damage, entity, POISON_DAMAGE, DamageType::Poison);
+                    }
+                    StatusKind::Regenerating => {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + REGENERATION_HEAL);
+                    }
+                    _ => {}
+                }
+                effect.turns -= 1;
+            }
+
+            for effect in status.effects.iter().filter(|effect| effect.turns <= 0) {
+                // Sight comes back straight away
+                if effect.kind == StatusKind::Blinded {
+                    if let Some(viewshed) = viewsheds.get_mut(entity) {
+                        viewshed.dirty = true;
+                    }
+                }
+
+                if is_player {
+                    log.entries.push(format!("You are no longer {}.", effect.kind.name().to_lowercase()));
+                } else if effect.kind == StatusKind::Asleep {
+                    if let Some(name) = names.get(entity) {
+                        log.entries.push(format!("{} wakes up.", name.name));
+                    }
+                }
+            }
+            status.effects.retain(|effect| effect.turns > 0);
+        }
+    }
+}
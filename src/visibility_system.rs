@@ -1,26 +1,33 @@
 use specs::prelude::*;
-use super::{Viewshed, Position, Map, Player};
-use bracket_lib::prelude::{field_of_view, Point};
+use super::{Viewshed, Position, Map, Player, Monster, Item, Telepathic, DetectingItems, GameTurn, GameLog,
+    StatusEffects, StatusKind};
+use bracket_lib::prelude::Point;
 
 pub struct VisibilitySystem {}
 
 impl<'a> System<'a> for VisibilitySystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = ( WriteExpect<'a, Map>,
                         Entities<'a>,
                         WriteStorage<'a, Viewshed>,
                         WriteStorage<'a, Position>,
-                        ReadStorage<'a, Player>
+                        ReadStorage<'a, Player>,
+                        ReadStorage<'a, StatusEffects>
                     );
     
     fn run (&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, statuses) = data;
 
         for (ent,viewshed,pos) in (&entities, &mut viewshed, &pos).join() {
-            if viewshed.dirty {
+            // The blind only know the tile they're standing on
+            let blind = statuses.get(ent).is_some_and(|s| s.has(StatusKind::Blinded));
+            if viewshed.dirty || blind {
                 viewshed.dirty = false;
-                viewshed.visible_tiles.clear();
-                viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
-                viewshed.visible_tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y <= map.height );
+                if blind {
+                    viewshed.visible_tiles = vec![Point::new(pos.x, pos.y)];
+                } else {
+                    viewshed.visible_tiles = map.field_of_view(Point::new(pos.x, pos.y), viewshed.range);
+                }
 
                 // If this is the player, then we can reveal what the player can see
                 // TODO Extend this for monsters too?
@@ -32,7 +39,7 @@ impl<'a> System<'a> for VisibilitySystem {
 
                     for vis in viewshed.visible_tiles.iter() {
                         let idx = map.xy_idx(vis.x, vis.y);
-                        map.revealed_tiles[idx] = true;
+                        map.reveal_tile(idx);
                         map.visible_tiles[idx] = true;
                     }
                 }
@@ -50,3 +57,143 @@ impl<'a> System<'a> for VisibilitySystem {
         }
     }
 }
+
+/// Works out which monsters and items the player senses through telepathy or detection, and
+/// wears those senses off when they expire. Sensing ignores walls, and is run after everything
+/// has moved so the player senses things where they actually are.
+pub struct SensingSystem {}
+
+impl<'a> System<'a> for SensingSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( WriteExpect<'a, Map>,
+                        ReadExpect<'a, GameTurn>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Player>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Item>,
+                        WriteStorage<'a, Telepathic>,
+                        WriteStorage<'a, DetectingItems>
+                    );
+
+    fn run (&mut self, data: Self::SystemData) {
+        let (mut map, turn, mut log, entities, pos, player, monsters, items, mut telepathic, mut detecting) = data;
+
+        map.sensed.clear();
+
+        let mut expired: Vec<Entity> = Vec::new();
+        for (ent, _player, player_pos, sense) in (&entities, &player, &pos, &telepathic).join() {
+            if turn.number >= sense.expires {
+                expired.push(ent);
+                log.entries.push("Your mind goes quiet.".to_string());
+                continue;
+            }
+            let sensed = map.entities_in_radius(Point::new(player_pos.x, player_pos.y), sense.range).into_iter()
+                .filter(|other| monsters.contains(*other))
+                .collect::<Vec<_>>();
+            map.sensed.extend(sensed);
+        }
+        for ent in expired.drain(..) {
+            telepathic.remove(ent);
+        }
+
+        for (ent, _player, player_pos, sense) in (&entities, &player, &pos, &detecting).join() {
+            if turn.number >= sense.expires {
+                expired.push(ent);
+                log.entries.push("You lose your sense of nearby objects.".to_string());
+                continue;
+            }
+            let sensed = map.entities_in_radius(Point::new(player_pos.x, player_pos.y), sense.range).into_iter()
+                .filter(|other| items.contains(*other))
+                .collect::<Vec<_>>();
+            map.sensed.extend(sensed);
+        }
+        for ent in expired.drain(..) {
+            detecting.remove(ent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{register_components, spawner, Name, MapIndexingSystem, PositionReader, TileType};
+
+    /// A room with the player in it, and another beyond a wall at x 10 that they can't see into.
+    fn world() -> (World, Entity) {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        let reader = ecs.write_storage::<Position>().register_reader();
+        ecs.insert(PositionReader{ reader });
+        let mut map = Map::new(20, 10);
+        for y in 1..9 {
+            for x in 1..19 {
+                if x != 10 {
+                    let idx = map.xy_idx(x, y);
+                    map.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+        map.populate_blocked();
+        ecs.insert(map);
+        ecs.insert(bracket_lib::prelude::RandomNumberGenerator::seeded(1));
+        ecs.insert(GameLog{ entries: Vec::new() });
+        ecs.insert(GameTurn{ number: 0 });
+        ecs.insert(Point::new(8, 5));
+        let player = spawner::player(&mut ecs, 8, 5);
+        ecs.insert(player);
+        ecs.write_storage::<Telepathic>().insert(player, Telepathic{ range: 8, expires: 100 }).unwrap();
+        (ecs, player)
+    }
+
+    fn sense(ecs: &mut World) {
+        MapIndexingSystem{}.run_now(ecs);
+        VisibilitySystem{}.run_now(ecs);
+        SensingSystem{}.run_now(ecs);
+    }
+
+    #[test]
+    fn telepathy_senses_the_monster_but_not_what_it_stands_on() {
+        let (mut ecs, _player) = world();
+        spawner::orc(&mut ecs, 12, 5);
+        let orc = (&ecs.entities(), &ecs.read_storage::<Monster>()).join().map(|(entity, _monster)| entity).next().unwrap();
+        let loot = ecs.create_entity().with(Position{ x: 12, y: 5 }).with(Item{}).with(Name{ name: "Dagger".to_string() }).build();
+        sense(&mut ecs);
+
+        let map = ecs.fetch::<Map>();
+        assert!(!map.visible_tiles[map.xy_idx(12, 5)]);
+        assert!(map.sensed.contains(&orc));
+        assert!(!map.sensed.contains(&loot));
+    }
+
+    #[test]
+    fn sensed_monster_is_followed_as_it_moves() {
+        let (mut ecs, _player) = world();
+        spawner::orc(&mut ecs, 12, 5);
+        let orc = (&ecs.entities(), &ecs.read_storage::<Monster>()).join().map(|(entity, _monster)| entity).next().unwrap();
+        sense(&mut ecs);
+        {
+            let mut map = ecs.fetch_mut::<Map>();
+            let idx = map.xy_idx(13, 6);
+            map.move_entity(orc, idx);
+            *ecs.write_storage::<Position>().get_mut(orc).unwrap() = Position{ x: 13, y: 6 };
+        }
+        SensingSystem{}.run_now(&ecs);
+
+        let map = ecs.fetch::<Map>();
+        assert!(map.sensed.contains(&orc));
+        assert_eq!(map.sensed.len(), 1);
+    }
+
+    #[test]
+    fn telepathy_wears_off() {
+        let (mut ecs, player) = world();
+        spawner::orc(&mut ecs, 12, 5);
+        ecs.write_resource::<GameTurn>().number = 100;
+        sense(&mut ecs);
+
+        assert!(ecs.fetch::<Map>().sensed.is_empty());
+        assert!(!ecs.read_storage::<Telepathic>().contains(player));
+    }
+}